@@ -0,0 +1,159 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::f32::consts::TAU;
+
+use crate::level::{NamedPath, PlayerPath};
+use crate::math::move_toward;
+use crate::player::Player;
+use crate::rail::{PlayerRail, RailSpace};
+use crate::velocity::Velocity;
+
+pub struct BehaviourPlugin;
+
+impl Plugin for BehaviourPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(apply_behaviours);
+    }
+}
+
+/// How an enemy moves, patterns are expressed relative to the frame the enemy was spawned in,
+/// for entities in rail space that frame is relative to the rail
+#[derive(Clone, Debug, Deserialize)]
+pub enum MovementPattern {
+    /// fly forward
+    Straight,
+    /// fly forward while weaving side to side
+    SineWeave { amplitude: f32, frequency: f32 },
+    /// fly forward while circling around the forward axis
+    CircleStrafe { radius: f32, frequency: f32 },
+    /// fly forward for a while then turn towards the player, turn_rate is in units per second
+    DiveAtPlayer { delay: f32, turn_rate: f32 },
+    /// follow a path from the level tagged with PATH::ENEMY::<name>, paths are always in world space
+    FollowPath { name: String },
+}
+
+#[derive(Component)]
+pub struct Behaviour {
+    pub pattern: MovementPattern,
+    pub speed: f32,
+    /// orientation of the frame the pattern is expressed in
+    pub frame: Quat,
+    /// offset from the pattern in the local frame, keeps formations apart when following a path
+    pub offset: Vec3,
+    pub elapsed: f32,
+    pub distance_along_path: f32,
+}
+
+impl Behaviour {
+    pub fn new(pattern: MovementPattern, speed: f32, frame: Quat) -> Self {
+        Self {
+            pattern,
+            speed,
+            frame,
+            offset: Vec3::ZERO,
+            elapsed: 0.0,
+            distance_along_path: 0.0,
+        }
+    }
+
+    pub fn with_offset(mut self, offset: Vec3) -> Self {
+        self.offset = offset;
+        self
+    }
+}
+
+/// drive velocity (or transform for path followers) from each movement pattern
+fn apply_behaviours(
+    mut query: Query<
+        (
+            &mut Behaviour,
+            &mut Velocity,
+            &mut Transform,
+            Option<&mut RailSpace>,
+        ),
+        Without<Player>,
+    >,
+    player_query: Query<&GlobalTransform, With<Player>>,
+    path_query: Query<(&PlayerPath, &NamedPath)>,
+    rail: Res<PlayerRail>,
+    time: Res<Time>,
+) {
+    let player_position = player_query.iter().next().map(|p| p.translation());
+
+    for (mut behaviour, mut velocity, mut transform, mut rail_space) in query.iter_mut() {
+        behaviour.elapsed += time.delta_seconds();
+
+        let forward = behaviour.frame * Vec3::NEG_Z;
+        let right = behaviour.frame * Vec3::X;
+        let up = behaviour.frame * Vec3::Y;
+        let t = behaviour.elapsed;
+
+        match behaviour.pattern.clone() {
+            MovementPattern::Straight => {
+                velocity.0 = forward * behaviour.speed;
+            }
+            MovementPattern::SineWeave {
+                amplitude,
+                frequency,
+            } => {
+                // derivative of amplitude * sin(tau * frequency * t)
+                let w = TAU * frequency;
+                velocity.0 = forward * behaviour.speed + right * amplitude * w * (w * t).cos();
+            }
+            MovementPattern::CircleStrafe { radius, frequency } => {
+                // derivative of radius * (cos(wt), sin(wt)) in the right/up plane
+                let w = TAU * frequency;
+                velocity.0 = forward * behaviour.speed
+                    + right * -radius * w * (w * t).sin()
+                    + up * radius * w * (w * t).cos();
+            }
+            MovementPattern::DiveAtPlayer { delay, turn_rate } => {
+                let mut direction = velocity.0.try_normalize().unwrap_or(forward);
+                if let Some(player_position) = player_position {
+                    if t >= delay {
+                        // the player position in the same space as the velocity
+                        let to_player = match &rail_space {
+                            Some(rail_space) => (rail.offset - rail_space.offset)
+                                .extend(rail.distance_to(rail_space.distance)),
+                            None => player_position - transform.translation,
+                        }
+                        .normalize_or_zero();
+                        direction =
+                            move_toward(direction, to_player, turn_rate * time.delta_seconds())
+                                .normalize_or_zero();
+                    }
+                }
+                velocity.0 = direction * behaviour.speed;
+                if direction != Vec3::ZERO {
+                    let rotation = Transform::IDENTITY.looking_at(direction, Vec3::Y).rotation;
+                    match rail_space.as_mut() {
+                        Some(rail_space) => rail_space.rotation = rotation,
+                        None => transform.rotation = rotation,
+                    }
+                }
+            }
+            MovementPattern::FollowPath { name } => {
+                let path = path_query
+                    .iter()
+                    .find(|(_, path_name)| path_name.0 == name)
+                    .map(|(path, _)| path);
+                match path {
+                    Some(path) if behaviour.distance_along_path < path.path_length => {
+                        behaviour.distance_along_path += behaviour.speed * time.delta_seconds();
+                        let next_transform =
+                            path.lerp_next_transform(behaviour.distance_along_path);
+                        transform.translation =
+                            next_transform.translation + next_transform.rotation * behaviour.offset;
+                        transform.rotation = next_transform.rotation;
+                        velocity.0 = Vec3::ZERO;
+                    }
+                    _ => {
+                        // missing path or end of the path, keep flying the way we are facing
+                        behaviour.frame = transform.rotation;
+                        behaviour.pattern = MovementPattern::Straight;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::{CollisionEvent, RapierContext};
+use bevy_rapier3d::rapier::geometry::CollisionEventFlags;
+use std::time::{Duration, Instant};
+
+use crate::bullet::{handle_collision_events, Bullet};
+use crate::events::{DamageEvent, EffectEvent};
+use crate::faction::Faction;
+use crate::health::Damage;
+use crate::pool::EntityPools;
+use crate::velocity::Velocity;
+
+const PROJECTILE_COUNTS: [usize; 4] = [100, 1_000, 5_000, 20_000];
+const RUNS: usize = 5;
+/// how much slower per event the largest count may be than the smallest
+const MAX_SLOWDOWN: f64 = 4.0;
+
+/// time collision dispatch headless with thousands of projectiles, every projectile hits a target,
+/// a flat time per event means dispatch does not depend on how many projectiles are alive
+/// run with `cargo test --release -- --ignored --nocapture collision_dispatch_scales`
+#[test]
+#[ignore]
+fn collision_dispatch_scales() {
+    println!("Collision dispatch benchmark, best of {} runs", RUNS);
+    let ns_per_event: Vec<f64> = PROJECTILE_COUNTS
+        .iter()
+        .map(|&count| {
+            let best = (0..RUNS).map(|_| time_dispatch(count)).min().unwrap();
+            let ns = best.as_nanos() as f64 / count as f64;
+            println!(
+                "{:>6} projectiles: {:>8.3} ms, {:>6.0} ns per event",
+                count,
+                best.as_secs_f64() * 1000.0,
+                ns
+            );
+            ns
+        })
+        .collect();
+
+    let first = ns_per_event[0];
+    let last = ns_per_event[ns_per_event.len() - 1];
+    assert!(
+        last <= first * MAX_SLOWDOWN,
+        "dispatch slowed from {:.0} to {:.0} ns per event",
+        first,
+        last
+    );
+}
+
+fn time_dispatch(count: usize) -> Duration {
+    let mut world = World::new();
+    world.init_resource::<Events<CollisionEvent>>();
+    world.init_resource::<Events<DamageEvent>>();
+    world.init_resource::<Events<EffectEvent>>();
+    world.init_resource::<EntityPools>();
+    world.init_resource::<RapierContext>();
+
+    let pairs: Vec<(Entity, Entity)> = (0..count)
+        .map(|_| {
+            let bullet = world
+                .spawn((
+                    Bullet::new(Vec3::ZERO),
+                    Transform::default(),
+                    Velocity(Vec3::NEG_Z),
+                    Faction::Player,
+                    Damage(1.0),
+                ))
+                .id();
+            let target = world.spawn(Faction::Enemy).id();
+            (bullet, target)
+        })
+        .collect();
+
+    let mut events = world.resource_mut::<Events<CollisionEvent>>();
+    for (i, (bullet, target)) in pairs.iter().enumerate() {
+        // rapier gives no order for the two handles
+        let (h1, h2) = if i % 2 == 0 {
+            (*bullet, *target)
+        } else {
+            (*target, *bullet)
+        };
+        events.send(CollisionEvent::Started(
+            h1,
+            h2,
+            CollisionEventFlags::empty(),
+        ));
+    }
+
+    let mut schedule = Schedule::new();
+    schedule.add_system(handle_collision_events);
+
+    let start = Instant::now();
+    schedule.run(&mut world);
+    let elapsed = start.elapsed();
+
+    let damage_events = world.resource::<Events<DamageEvent>>().len();
+    assert_eq!(damage_events, count, "every projectile should have hit");
+    elapsed
+}
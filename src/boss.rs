@@ -0,0 +1,303 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use serde::Deserialize;
+
+use crate::difficulty::Difficulty;
+use crate::enemy_registry::{EnemyAssets, EnemyRegistry};
+use crate::enemy_weapon::FirePattern;
+use crate::events::{EffectEvent, EffectKind, SpawnBossEvent, TeardownLevelEvent};
+use crate::faction::Faction;
+use crate::health::Health;
+use crate::player::{PathFollower, PlayerRoot};
+use crate::weapon::Weapon;
+
+const ARENA_TRIGGER_DISTANCE: f32 = 120.0;
+#[cfg(feature = "dev")]
+const DEBUG_ARENA_DISTANCE: f32 = 150.0;
+/// the boss in the enemy registry an arena spawns
+const BOSS_TYPE: &str = "boss";
+const BOSS_EFFECT_SIZE: f32 = 12.0;
+
+pub struct BossPlugin;
+
+impl Plugin for BossPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(trigger_boss_arena)
+            .add_system(spawn_boss)
+            .add_system(update_boss)
+            .add_system(on_teardown);
+        #[cfg(feature = "dev")]
+        app.add_system(debug_spawn_arena);
+    }
+}
+
+/// A point in the level where the player stops on the rail until the boss is defeated,
+/// placed with the ARENA::BOSS gltf extra
+#[derive(Component, Default)]
+pub struct BossArena {
+    pub triggered: bool,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum BossPartKind {
+    /// the boss is defeated once every weak point is destroyed
+    WeakPoint,
+    /// blocks shots aimed at the weak points behind it
+    Armor,
+}
+
+#[derive(Component)]
+pub struct BossPart {
+    pub kind: BossPartKind,
+}
+
+/// A part of the boss, offsets are in the boss frame where -z faces the player
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct BossPartDescriptor {
+    pub kind: BossPartKind,
+    pub offset: (f32, f32, f32),
+    pub half_extents: (f32, f32, f32),
+    pub health: f32,
+}
+
+/// Attack pattern used while the weak points have at most health_fraction of their total health
+#[derive(Clone, Debug, Deserialize)]
+pub struct BossPhase {
+    pub health_fraction: f32,
+    pub fire_pattern: FirePattern,
+    pub fire_rate: f32,
+}
+
+#[derive(Component)]
+pub struct Boss {
+    pub phases: Vec<BossPhase>,
+    pub phase: usize,
+    pub max_health: f32,
+}
+
+/// build the boss an arena asked for from its entry in the enemy registry
+fn spawn_boss(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    enemy_assets: Option<Res<EnemyAssets>>,
+    registries: Res<Assets<EnemyRegistry>>,
+    mut ev_spawn: EventReader<SpawnBossEvent>,
+) {
+    let Some(registry) = enemy_assets.and_then(|assets| registries.get(&assets.registry)) else {
+        return;
+    };
+    for ev in ev_spawn.iter() {
+        let Some(archetype) = registry.bosses.get(&ev.boss) else {
+            println!("Unknown boss type: {}", ev.boss);
+            continue;
+        };
+        let Some(first_phase) = archetype.phases.first() else {
+            println!("Boss type without phases: {}", ev.boss);
+            continue;
+        };
+        let max_health = archetype
+            .parts
+            .iter()
+            .filter(|part| matches!(part.kind, BossPartKind::WeakPoint))
+            .map(|part| part.health)
+            .sum();
+
+        let armor_material = materials.add(StandardMaterial {
+            base_color: Color::DARK_GRAY,
+            ..default()
+        });
+        let weak_point_material = materials.add(StandardMaterial {
+            base_color: Color::RED,
+            emissive: Color::rgb(2.0, 0.2, 0.2),
+            ..default()
+        });
+
+        commands
+            .spawn((
+                SpatialBundle::from_transform(ev.transform),
+                Boss {
+                    phase: 0,
+                    max_health,
+                    phases: archetype.phases.clone(),
+                },
+                Faction::Enemy,
+                Weapon::new(first_phase.fire_rate, 0.0, 0.0, 0.0),
+                first_phase.fire_pattern.clone(),
+                // the parts are colliders attached to this body
+                RigidBody::KinematicPositionBased,
+            ))
+            .with_children(|boss| {
+                boss.spawn(SceneBundle {
+                    scene: asset_server.load(archetype.model.as_str()),
+                    transform: Transform::from_scale(Vec3::splat(archetype.model_scale)),
+                    ..default()
+                });
+                for part in archetype.parts.iter() {
+                    let (x, y, z) = part.offset;
+                    let (half_x, half_y, half_z) = part.half_extents;
+                    boss.spawn((
+                        PbrBundle {
+                            mesh: meshes.add(Mesh::from(shape::Box::new(
+                                half_x * 2.0,
+                                half_y * 2.0,
+                                half_z * 2.0,
+                            ))),
+                            material: match part.kind {
+                                BossPartKind::WeakPoint => weak_point_material.clone(),
+                                BossPartKind::Armor => armor_material.clone(),
+                            },
+                            transform: Transform::from_xyz(x, y, z),
+                            ..default()
+                        },
+                        BossPart { kind: part.kind },
+                        Health::new(part.health),
+                        Faction::Enemy,
+                        Collider::cuboid(half_x, half_y, half_z),
+                        Faction::Enemy.body_groups(),
+                        ActiveEvents::COLLISION_EVENTS,
+                        // bullets have no rigid body, so they count as static colliders
+                        ActiveCollisionTypes::default() | ActiveCollisionTypes::KINEMATIC_STATIC,
+                    ));
+                }
+            });
+    }
+}
+
+/// stop the player on the rail and spawn the boss once the player reaches an arena
+fn trigger_boss_arena(
+    mut arena_query: Query<(&GlobalTransform, &mut BossArena)>,
+    mut player_query: Query<(&GlobalTransform, &mut PathFollower), With<PlayerRoot>>,
+    enemy_assets: Option<Res<EnemyAssets>>,
+    registries: Res<Assets<EnemyRegistry>>,
+    mut ev_spawn: EventWriter<SpawnBossEvent>,
+) {
+    let Some(registry) = enemy_assets.and_then(|assets| registries.get(&assets.registry)) else {
+        return;
+    };
+    for (arena_transform, mut arena) in arena_query.iter_mut() {
+        if arena.triggered {
+            continue;
+        }
+        for (player_transform, mut path_follower) in player_query.iter_mut() {
+            let arena_position = arena_transform.translation();
+            let player_position = player_transform.translation();
+            if arena_position.distance(player_position) > ARENA_TRIGGER_DISTANCE {
+                continue;
+            }
+
+            arena.triggered = true;
+            // don't hold the player for a boss that will never spawn
+            if !registry.bosses.contains_key(BOSS_TYPE) {
+                println!("Unknown boss type: {}", BOSS_TYPE);
+                continue;
+            }
+            path_follower.hold = true;
+            ev_spawn.send(SpawnBossEvent {
+                boss: BOSS_TYPE.to_string(),
+                transform: Transform::from_translation(arena_position)
+                    .looking_at(player_position, Vec3::Y),
+            });
+        }
+    }
+}
+
+/// a boss with the weapon its phases change, weak points are among its children
+type BossQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static GlobalTransform,
+        &'static mut Boss,
+        &'static mut Weapon,
+        &'static mut FirePattern,
+        Option<&'static Children>,
+    ),
+>;
+
+/// bosses and arenas spawned outside the level, arenas placed in the level go with it
+type TeardownFilter = Or<(With<Boss>, (With<BossArena>, Without<Parent>))>;
+
+/// switch attack phases as weak points take damage, release the player once they are all gone
+fn update_boss(
+    mut commands: Commands,
+    mut boss_query: BossQuery,
+    part_query: Query<(&BossPart, &Health)>,
+    mut follower_query: Query<&mut PathFollower>,
+    mut ev_effect: EventWriter<EffectEvent>,
+    difficulty: Res<Difficulty>,
+) {
+    for (entity, global_transform, mut boss, mut weapon, mut fire_pattern, children) in
+        boss_query.iter_mut()
+    {
+        let mut health = 0.0;
+        let mut weak_points = 0;
+        for child in children.into_iter().flat_map(|children| children.iter()) {
+            if let Ok((part, part_health)) = part_query.get(*child) {
+                if matches!(part.kind, BossPartKind::WeakPoint) && !part_health.is_dead() {
+                    health += part_health.current;
+                    weak_points += 1;
+                }
+            }
+        }
+
+        if weak_points == 0 {
+            ev_effect.send(EffectEvent {
+                kind: EffectKind::Destruction,
+                position: global_transform.translation(),
+                normal: global_transform.up(),
+                size: BOSS_EFFECT_SIZE,
+            });
+            commands.entity(entity).despawn_recursive();
+            for mut path_follower in follower_query.iter_mut() {
+                path_follower.hold = false;
+            }
+            continue;
+        }
+
+        let fraction = health / boss.max_health;
+        while boss.phase + 1 < boss.phases.len()
+            && fraction <= boss.phases[boss.phase + 1].health_fraction
+        {
+            boss.phase += 1;
+            let phase = boss.phases[boss.phase].clone();
+            *fire_pattern = phase.fire_pattern;
+            weapon.fire_rate = phase.fire_rate * difficulty.enemy_fire_rate();
+        }
+    }
+}
+
+// place an arena ahead of the player, levels without an ARENA::BOSS extra can still test the boss
+// @TODO: Use an input map
+#[cfg(feature = "dev")]
+fn debug_spawn_arena(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    player_query: Query<&GlobalTransform, With<PlayerRoot>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::B) {
+        return;
+    }
+    for player_transform in player_query.iter() {
+        let position =
+            player_transform.translation() + player_transform.forward() * DEBUG_ARENA_DISTANCE;
+        commands.spawn((
+            SpatialBundle::from_transform(Transform::from_translation(position)),
+            BossArena::default(),
+        ));
+    }
+}
+
+fn on_teardown(
+    mut commands: Commands,
+    query: Query<Entity, TeardownFilter>,
+    mut ev_teardown: EventReader<TeardownLevelEvent>,
+) {
+    for _ in ev_teardown.iter() {
+        for entity in query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
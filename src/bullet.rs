@@ -1,23 +1,209 @@
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
-use bevy_rapier3d::prelude::{Collider, CollisionEvent, Sensor};
+use bevy_rapier3d::prelude::{
+    ActiveCollisionTypes, ActiveEvents, Collider, CollisionEvent, CollisionGroups, QueryFilter,
+    RapierConfiguration, RapierContext, Sensor, TOIStatus,
+};
+
+use serde::Deserialize;
 
 use crate::{
-    events::{ExplosionEvent, SpawnBulletEvent, TeardownLevelEvent},
+    constants::BOUNDS_POS,
+    enemy_weapon::FirePattern,
+    events::{DamageEvent, EffectEvent, EffectKind, SpawnBulletEvent, TeardownLevelEvent},
+    faction::{Faction, TERRAIN_GROUP},
+    health::{Damage, Health},
+    lifetime::{Lifetime, MaxRange},
+    pool::{EntityPools, PoolCommandsExt, BULLET_MODEL},
     velocity::Velocity,
 };
 
+const BULLET_LIFETIME: f32 = 2.5;
+const BULLET_RANGE: f32 = BOUNDS_POS.z;
+const MISSILE_SCALE: f32 = 1.5;
+/// missiles only lock on to targets this close
+const MISSILE_LOCK_RANGE: f32 = 200.0;
+/// a beam stays on this long after its last shot, firing again keeps it alive
+const BEAM_HOLD_TIME: f32 = 0.15;
+const BEAM_WIDTH: f32 = 0.3;
+/// effect size of a bullet hitting the level
+const IMPACT_SIZE: f32 = 1.0;
+/// damage both ships take when ships of different factions collide
+const RAM_DAMAGE: f32 = 3.0;
+
+/// ships that can be rammed, bullets carry their own damage
+type ShipFilter = (With<Health>, Without<Bullet>);
+
 pub struct BulletPlugin;
 
 impl Plugin for BulletPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(handle_collision_events)
+        app.add_startup_system(setup_beam_assets)
+            .add_system(handle_collision_events)
+            .add_system(sweep_bullets)
             .add_system(create_bullet)
+            .add_system(steer_missiles)
+            .add_system(update_beams)
             .add_system(on_teardown);
     }
 }
 
+/// What a weapon fires, carried by the spawn bullet event
+#[derive(Component, Clone, Copy, Debug, Default, Deserialize)]
+pub enum ProjectileKind {
+    #[default]
+    Bolt,
+    /// a fan of bolts spread horizontally around the direction, angle in degrees
+    Spread { count: u32, angle: f32 },
+    /// a bolt that steers towards the nearest target, turn rate in radians per second
+    Missile { turn_rate: f32 },
+    /// a continuous ray from the shooter that damages whatever it touches every second
+    Beam { length: f32, damage_per_second: f32 },
+}
+
+/// steers the velocity of a projectile towards the nearest target of another faction
+#[derive(Component)]
+pub struct Homing {
+    pub turn_rate: f32,
+}
+
+/// A ray attached to its shooter, its mesh is stretched along -z to whatever it hits
+#[derive(Component)]
+pub struct Beam {
+    pub length: f32,
+    pub damage_per_second: f32,
+    pub faction: Faction,
+}
+
+#[derive(Resource)]
+struct BeamAssets {
+    mesh: Handle<Mesh>,
+    player_material: Handle<StandardMaterial>,
+    enemy_material: Handle<StandardMaterial>,
+}
+
+/// A projectile, hits are swept from its last position so fast bullets can't skip past anything
 #[derive(Component)]
-pub struct Bullet;
+pub struct Bullet {
+    last_position: Vec3,
+    /// already hit something this frame, the despawn has not been applied yet
+    spent: bool,
+}
+
+impl Bullet {
+    pub fn new(position: Vec3) -> Self {
+        Self {
+            last_position: position,
+            spent: false,
+        }
+    }
+}
+
+/// what happens to a bullet touching another entity
+enum BulletHit {
+    PassThrough,
+    Blocked,
+    Damage,
+}
+
+fn classify_hit(
+    other: Entity,
+    bullet_faction: &Faction,
+    query_faction: &Query<&Faction, Without<Bullet>>,
+    query_groups: &Query<&CollisionGroups>,
+) -> BulletHit {
+    // the level stops bullets without taking damage
+    if let Ok(groups) = query_groups.get(other) {
+        if groups.memberships.contains(TERRAIN_GROUP) {
+            return BulletHit::Blocked;
+        }
+    }
+    // collision groups keep bullets off their own faction, this guards the rest
+    match query_faction.get(other) {
+        Ok(faction) if faction != bullet_faction => BulletHit::Damage,
+        _ => BulletHit::PassThrough,
+    }
+}
+
+/// damage the target or spark against the level, returns whether the bullet is used up
+fn send_hit(
+    hit: BulletHit,
+    damage: DamageEvent,
+    ev_damage: &mut EventWriter<DamageEvent>,
+    ev_effect: &mut EventWriter<EffectEvent>,
+) -> bool {
+    match hit {
+        BulletHit::PassThrough => return false,
+        // hulls pick their own effect once the damage is applied
+        BulletHit::Blocked => ev_effect.send(EffectEvent {
+            kind: EffectKind::Impact,
+            position: damage.point,
+            normal: damage.normal,
+            size: IMPACT_SIZE,
+        }),
+        BulletHit::Damage => ev_damage.send(damage),
+    }
+    true
+}
+
+/// the closest point on the target's surface to a bullet and the outward normal there,
+/// falls back to the bullet position and against its direction when the target has no collider
+fn surface_hit(
+    rapier_context: &RapierContext,
+    target: Entity,
+    position: Vec3,
+    direction: Vec3,
+) -> (Vec3, Vec3) {
+    let is_target = |entity: Entity| entity == target;
+    let filter = QueryFilter::new().predicate(&is_target);
+    let fallback = (position, -direction.normalize_or_zero());
+    let Some((_, projection)) = rapier_context.project_point(position, false, filter) else {
+        return fallback;
+    };
+    // from inside the surface points back out past the bullet
+    let outward = if projection.is_inside {
+        projection.point - position
+    } else {
+        position - projection.point
+    };
+    match outward.try_normalize() {
+        Some(normal) => (projection.point, normal),
+        None => fallback,
+    }
+}
+
+/// ships of different factions that touch both take ram damage
+fn send_ram(
+    a: Entity,
+    b: Entity,
+    query_ship: &Query<(&Faction, &GlobalTransform), ShipFilter>,
+    ev_damage: &mut EventWriter<DamageEvent>,
+) {
+    let (Ok((faction_a, transform_a)), Ok((faction_b, transform_b))) =
+        (query_ship.get(a), query_ship.get(b))
+    else {
+        return;
+    };
+    if faction_a == faction_b {
+        return;
+    }
+    let position_a = transform_a.translation();
+    let position_b = transform_b.translation();
+    let point = position_a.lerp(position_b, 0.5);
+    for (target, source, from, to) in [
+        (a, *faction_b, position_a, position_b),
+        (b, *faction_a, position_b, position_a),
+    ] {
+        ev_damage.send(DamageEvent {
+            target,
+            amount: RAM_DAMAGE,
+            source,
+            point,
+            normal: (to - from).normalize_or_zero(),
+            continuous: false,
+        });
+    }
+}
 
 fn on_teardown(
     mut commands: Commands,
@@ -26,51 +212,391 @@ fn on_teardown(
 ) {
     for _ in ev_teardown.iter() {
         for entity in query_bullet.iter() {
-            commands.entity(entity).despawn_recursive();
+            commands.despawn_pooled(entity);
         }
     }
 }
 
+fn setup_beam_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let half_width = BEAM_WIDTH / 2.0;
+    // unit length from the origin along -z, scaled to the beam length
+    let mesh = meshes.add(Mesh::from(shape::Box {
+        min_x: -half_width,
+        max_x: half_width,
+        min_y: -half_width,
+        max_y: half_width,
+        min_z: -1.0,
+        max_z: 0.0,
+    }));
+    let mut beam_material = |color: Color| {
+        materials.add(StandardMaterial {
+            base_color: color,
+            emissive: color * 4.0,
+            unlit: true,
+            ..default()
+        })
+    };
+    commands.insert_resource(BeamAssets {
+        mesh,
+        player_material: beam_material(Color::CYAN),
+        enemy_material: beam_material(Color::ORANGE_RED),
+    });
+}
+
+fn spawn_bolt(
+    commands: &mut Commands,
+    pools: &mut EntityPools,
+    asset_server: &AssetServer,
+    ev: &SpawnBulletEvent,
+    transform: Transform,
+    direction: Vec3,
+) -> Entity {
+    let bullet = commands
+        .spawn((
+            SpatialBundle::from_transform(transform),
+            Velocity(direction * ev.speed),
+            Bullet::new(transform.translation),
+            ev.faction,
+            Damage(ev.damage),
+            Lifetime::new(BULLET_LIFETIME),
+            MaxRange::new(transform.translation, BULLET_RANGE),
+            Collider::cuboid(0.494, 0.494, 2.144),
+            Sensor,
+            ev.faction.projectile_groups(),
+            ActiveEvents::COLLISION_EVENTS,
+            // level colliders have no rigid body either
+            ActiveCollisionTypes::default() | ActiveCollisionTypes::STATIC_STATIC,
+        ))
+        .id();
+    pools.attach_model(commands, asset_server, bullet, BULLET_MODEL);
+    bullet
+}
+
 fn create_bullet(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    beam_assets: Res<BeamAssets>,
+    mut pools: ResMut<EntityPools>,
+    shooter_query: Query<&GlobalTransform>,
+    mut beam_query: Query<(&Parent, &mut Lifetime), With<Beam>>,
     mut ev_bullet: EventReader<SpawnBulletEvent>,
 ) {
     for ev in ev_bullet.iter() {
-        commands.spawn((
-            SceneBundle {
-                scene: asset_server.load("models/Spaceship/bullet.gltf#Scene0"),
-                transform: ev.transform.clone(),
-                ..default()
-            },
-            Velocity(ev.direction * ev.speed),
-            Bullet,
-            Collider::cuboid(0.494, 0.494, 2.144),
-            Sensor,
-        ));
+        match ev.kind {
+            ProjectileKind::Bolt => {
+                spawn_bolt(
+                    &mut commands,
+                    &mut pools,
+                    &asset_server,
+                    ev,
+                    ev.transform,
+                    ev.direction,
+                );
+            }
+            ProjectileKind::Spread { count, angle } => {
+                for direction in (FirePattern::Spread { count, angle }).directions(ev.direction) {
+                    let position = ev.transform.translation;
+                    let transform = Transform::from_translation(position)
+                        .looking_at(position + direction, Vec3::Y);
+                    spawn_bolt(
+                        &mut commands,
+                        &mut pools,
+                        &asset_server,
+                        ev,
+                        transform,
+                        direction,
+                    );
+                }
+            }
+            ProjectileKind::Missile { turn_rate } => {
+                let transform = ev.transform.with_scale(Vec3::splat(MISSILE_SCALE));
+                let missile = spawn_bolt(
+                    &mut commands,
+                    &mut pools,
+                    &asset_server,
+                    ev,
+                    transform,
+                    ev.direction,
+                );
+                commands.entity(missile).insert(Homing { turn_rate });
+            }
+            ProjectileKind::Beam {
+                length,
+                damage_per_second,
+            } => {
+                // keep the shooter's current beam on instead of stacking another one
+                if let Some((_, mut lifetime)) = beam_query
+                    .iter_mut()
+                    .find(|(parent, _)| parent.get() == ev.shooter)
+                {
+                    lifetime.reset();
+                    continue;
+                }
+                let Ok(shooter_transform) = shooter_query.get(ev.shooter) else {
+                    continue;
+                };
+
+                // the beam follows the shooter, so place it in the shooter's space
+                let to_local = shooter_transform.affine().inverse();
+                let origin = to_local.transform_point3(ev.transform.translation);
+                let direction = to_local.transform_vector3(ev.direction).normalize_or_zero();
+                let beam = commands
+                    .spawn((
+                        PbrBundle {
+                            mesh: beam_assets.mesh.clone(),
+                            material: match ev.faction {
+                                Faction::Player => beam_assets.player_material.clone(),
+                                Faction::Enemy => beam_assets.enemy_material.clone(),
+                            },
+                            transform: Transform::from_translation(origin)
+                                .looking_at(origin + direction, Vec3::Y),
+                            ..default()
+                        },
+                        Beam {
+                            length,
+                            damage_per_second,
+                            faction: ev.faction,
+                        },
+                        Lifetime::new(BEAM_HOLD_TIME),
+                    ))
+                    .id();
+                commands.entity(ev.shooter).add_child(beam);
+            }
+        }
+    }
+}
+
+/// turn missiles towards the nearest target ahead of them
+fn steer_missiles(
+    mut missile_query: Query<(&Homing, &Faction, &mut Velocity, &mut Transform)>,
+    target_query: Query<(&GlobalTransform, &Faction), With<Health>>,
+    time: Res<Time>,
+) {
+    for (homing, faction, mut velocity, mut transform) in missile_query.iter_mut() {
+        let speed = velocity.0.length();
+        let Some(heading) = velocity.0.try_normalize() else {
+            continue;
+        };
+        let target = target_query
+            .iter()
+            .filter(|(_, target_faction)| *target_faction != faction)
+            .map(|(target_transform, _)| target_transform.translation() - transform.translation)
+            .filter(|to_target| {
+                to_target.dot(heading) > 0.0 && to_target.length() <= MISSILE_LOCK_RANGE
+            })
+            .min_by(|a, b| a.length_squared().total_cmp(&b.length_squared()));
+        let Some(desired) = target.and_then(|to_target| to_target.try_normalize()) else {
+            continue;
+        };
+
+        let angle = heading.angle_between(desired);
+        if angle <= 0.0 {
+            continue;
+        }
+        let t = (homing.turn_rate * time.delta_seconds() / angle).min(1.0);
+        let heading = Quat::IDENTITY.slerp(Quat::from_rotation_arc(heading, desired), t) * heading;
+        velocity.0 = heading * speed;
+        let position = transform.translation;
+        transform.look_at(position + heading, Vec3::Y);
+    }
+}
+
+/// cast every beam against the world, damage what it touches and stretch it up to the hit
+fn update_beams(
+    mut beam_query: Query<(&Beam, &GlobalTransform, &mut Transform)>,
+    query_faction: Query<&Faction, Without<Bullet>>,
+    query_groups: Query<&CollisionGroups>,
+    rapier_context: Res<RapierContext>,
+    time: Res<Time>,
+    mut ev_damage: EventWriter<DamageEvent>,
+) {
+    for (beam, global_transform, mut transform) in beam_query.iter_mut() {
+        let filter = QueryFilter::new()
+            .exclude_sensors()
+            .groups(beam.faction.projectile_groups());
+        let hit = rapier_context.cast_ray_and_get_normal(
+            global_transform.translation(),
+            global_transform.forward(),
+            beam.length,
+            true,
+            filter,
+        );
+
+        let mut length = beam.length;
+        if let Some((other, intersection)) = hit {
+            length = intersection.toi;
+            if let BulletHit::Damage =
+                classify_hit(other, &beam.faction, &query_faction, &query_groups)
+            {
+                ev_damage.send(DamageEvent {
+                    target: other,
+                    amount: beam.damage_per_second * time.delta_seconds(),
+                    source: beam.faction,
+                    point: intersection.point,
+                    normal: intersection.normal,
+                    continuous: true,
+                });
+            }
+        }
+        transform.scale.z = length;
+    }
+}
+
+/// what a bullet needs to land a hit, shared by the sweep and the collision events
+#[derive(SystemParam)]
+pub(crate) struct BulletHits<'w, 's> {
+    query_faction: Query<'w, 's, &'static Faction, Without<Bullet>>,
+    query_groups: Query<'w, 's, &'static CollisionGroups>,
+    rapier_context: Res<'w, RapierContext>,
+    commands: Commands<'w, 's>,
+    ev_damage: EventWriter<'w, DamageEvent>,
+    ev_effect: EventWriter<'w, EffectEvent>,
+}
+
+impl BulletHits<'_, '_> {
+    /// send the hit and despawn the bullet if it is used up, returns whether it was
+    fn land(&mut self, bullet: Entity, damage: DamageEvent) -> bool {
+        let hit = classify_hit(
+            damage.target,
+            &damage.source,
+            &self.query_faction,
+            &self.query_groups,
+        );
+        if !send_hit(hit, damage, &mut self.ev_damage, &mut self.ev_effect) {
+            return false;
+        }
+        self.commands.despawn_pooled(bullet);
+        true
+    }
+}
+
+type SweptBullet<'a> = (
+    Entity,
+    &'a mut Bullet,
+    &'a Transform,
+    &'a Collider,
+    &'a CollisionGroups,
+    &'a Faction,
+    &'a Damage,
+);
+
+/// cast each bullet's shape along the path it moved since last frame,
+/// catches the hits a fast bullet would tunnel through between physics steps
+fn sweep_bullets(
+    mut query_bullet: Query<SweptBullet<'static>>,
+    query_transform: Query<&GlobalTransform>,
+    rapier_config: Res<RapierConfiguration>,
+    mut hits: BulletHits,
+) {
+    for (entity, mut bullet, transform, collider, groups, faction, damage) in
+        query_bullet.iter_mut()
+    {
+        let start = bullet.last_position;
+        bullet.last_position = transform.translation;
+        if bullet.spent {
+            continue;
+        }
+
+        // a time of impact of 1 is the end of the movement
+        let filter = QueryFilter::new()
+            .exclude_sensors()
+            .exclude_collider(entity)
+            .groups(*groups);
+        let movement = transform.translation - start;
+        // rapier only scales the collider after a physics step, a bullet fired
+        // this frame or a missile taken from the pool would sweep at the wrong size
+        let mut shape = collider.clone();
+        shape.set_scale(transform.scale, rapier_config.scaled_shape_subdivision);
+        let Some((other, toi)) = hits.rapier_context.cast_shape(
+            start,
+            transform.rotation,
+            movement,
+            &shape,
+            1.0,
+            filter,
+        ) else {
+            continue;
+        };
+
+        // the witness and normal are local to the hit collider, which rapier scales
+        // itself so only its rotation and position apply
+        let (point, normal) = match (toi.status, query_transform.get(other)) {
+            (TOIStatus::Penetrating, _) | (_, Err(_)) => {
+                surface_hit(&hits.rapier_context, other, start, movement)
+            }
+            (_, Ok(other_transform)) => {
+                let (_, rotation, translation) = other_transform.to_scale_rotation_translation();
+                (
+                    translation + rotation * toi.witness2,
+                    rotation * toi.normal2,
+                )
+            }
+        };
+        let damage_event = DamageEvent {
+            target: other,
+            amount: damage.0,
+            source: *faction,
+            point,
+            normal,
+            continuous: false,
+        };
+        bullet.spent = hits.land(entity, damage_event);
     }
 }
 
-fn handle_collision_events(
-    query_bullet: Query<(Entity, &Transform), With<Bullet>>,
+/// overlaps reported by rapier, ex: a target moving into a slow bullet or two ships ramming,
+/// the roles of both entities are looked up directly so the cost only grows with the events
+pub(crate) fn handle_collision_events(
+    mut query_bullet: Query<(&mut Bullet, &Transform, &Velocity, &Faction, &Damage)>,
+    query_ship: Query<(&Faction, &GlobalTransform), ShipFilter>,
     mut contact_events: EventReader<CollisionEvent>,
-    mut commands: Commands,
-    mut ev_explosion: EventWriter<ExplosionEvent>,
+    mut hits: BulletHits,
 ) {
     for contact_event in contact_events.iter() {
-        for (bullet_entity, bullet_transform) in query_bullet.iter() {
-            if let CollisionEvent::Started(h1, h2, _event_flag) = contact_event {
-                if h1 == &bullet_entity || h2 == &bullet_entity {
-                    ev_explosion.send(ExplosionEvent(bullet_transform.clone()));
-
-                    if let Some(entity_commands) = commands.get_entity(*h1) {
-                        entity_commands.despawn_recursive();
-                    }
-                    if let Some(entity_commands) = commands.get_entity(*h2) {
-                        entity_commands.despawn_recursive();
-                    }
-                }
+        let CollisionEvent::Started(h1, h2, _event_flag) = contact_event else {
+            continue;
+        };
+        let (bullet_entity, other) = match (query_bullet.contains(*h1), query_bullet.contains(*h2))
+        {
+            (true, false) => (*h1, *h2),
+            (false, true) => (*h2, *h1),
+            // bullets never touch each other
+            (true, true) => continue,
+            (false, false) => {
+                send_ram(*h1, *h2, &query_ship, &mut hits.ev_damage);
+                continue;
             }
+        };
+
+        let Ok((mut bullet, transform, velocity, bullet_faction, damage)) =
+            query_bullet.get_mut(bullet_entity)
+        else {
+            continue;
+        };
+        // the sweep may have already used this bullet
+        if bullet.spent {
+            continue;
         }
+
+        // rapier gives no contact point for sensors, the bullet is already overlapping
+        let (point, normal) = surface_hit(
+            &hits.rapier_context,
+            other,
+            transform.translation,
+            velocity.0,
+        );
+        let damage_event = DamageEvent {
+            target: other,
+            amount: damage.0,
+            source: *bullet_faction,
+            point,
+            normal,
+            continuous: false,
+        };
+        bullet.spent = hits.land(bullet_entity, damage_event);
     }
 }
@@ -5,7 +5,7 @@ use bevy::{core_pipeline::bloom::BloomSettings, prelude::*, render::camera::Proj
 pub const BOUNDS_POS: Vec3 = Vec3::new(15.0, 8.0, 300.0);
 
 pub fn make_cam_entity(cam_transform: Transform) -> (bevy::prelude::Camera3dBundle, BloomSettings) {
-    return (
+    (
         Camera3dBundle {
             camera: Camera {
                 hdr: true, // disable to use rapier debug render pipeline
@@ -24,5 +24,5 @@ pub fn make_cam_entity(cam_transform: Transform) -> (bevy::prelude::Camera3dBund
             intensity: 0.05,
             ..default()
         },
-    );
+    )
 }
@@ -0,0 +1,353 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::{
+        Extent3d, TextureDimension, TextureFormat, TextureViewDescriptor, TextureViewDimension,
+    },
+};
+use std::f32::consts::PI;
+
+/// the largest finite half float
+const F16_MAX: f32 = 65504.0;
+
+/// Sky images that can't be used as a cube texture directly
+#[derive(Clone, Copy, Debug)]
+pub enum CubemapLayout {
+    /// one image with the six faces stacked vertically, ex: textures/space_cubemap.png
+    Stacked,
+    /// one 2:1 panorama, ex: an .hdr or .exr HDRI
+    Equirectangular,
+    /// six square images in the order +x, -x, +y, -y, +z, -z
+    Faces,
+    /// already a cube texture, ex: one built earlier and kept to switch back to
+    Cube,
+}
+
+impl CubemapLayout {
+    /// HDRIs are equirectangular, everything else is expected to be stacked
+    pub fn from_path(path: &str) -> Self {
+        let path = path.to_lowercase();
+        if path.ends_with(".hdr") || path.ends_with(".exr") {
+            CubemapLayout::Equirectangular
+        } else {
+            CubemapLayout::Stacked
+        }
+    }
+}
+
+/// reconfigure a vertical strip of six faces as a cube texture in place
+pub fn reinterpret_stacked(image: &mut Image) {
+    if image.texture_descriptor.array_layer_count() == 1 {
+        image.reinterpret_stacked_2d_as_array(
+            image.texture_descriptor.size.height / image.texture_descriptor.size.width,
+        );
+    }
+    image.texture_view_descriptor = Some(cube_view());
+}
+
+/// project a panorama onto the six faces of a cube texture, keeping the full hdr range
+pub fn equirectangular_to_cube(panorama: &Image) -> Option<Image> {
+    let width = panorama.texture_descriptor.size.width;
+    let height = panorama.texture_descriptor.size.height;
+    // each face covers a quarter of the panorama's width
+    let face_size = width / 4;
+    if face_size == 0 || height == 0 {
+        return None;
+    }
+    // fail early on formats that can't be read
+    read_texel(panorama, 0, 0)?;
+
+    let mut data = Vec::with_capacity((face_size * face_size * 6 * 8) as usize);
+    for face in 0..6 {
+        for y in 0..face_size {
+            for x in 0..face_size {
+                let u = 2.0 * (x as f32 + 0.5) / face_size as f32 - 1.0;
+                let v = 2.0 * (y as f32 + 0.5) / face_size as f32 - 1.0;
+                let color = sample_panorama(panorama, face_direction(face, u, v));
+                for channel in color.to_array() {
+                    data.extend_from_slice(&to_f16_bits(channel).to_le_bytes());
+                }
+            }
+        }
+    }
+
+    let mut image = Image::new(
+        Extent3d {
+            width: face_size,
+            height: face_size,
+            depth_or_array_layers: 6,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba16Float,
+    );
+    image.texture_view_descriptor = Some(cube_view());
+    Some(image)
+}
+
+/// stack six square images of the same size and format into a cube texture
+pub fn stack_faces(faces: &[&Image]) -> Option<Image> {
+    let first = faces.first()?;
+    let size = first.texture_descriptor.size;
+    let format = first.texture_descriptor.format;
+    if faces.len() != 6 || size.width != size.height {
+        return None;
+    }
+    if faces.iter().any(|face| {
+        face.texture_descriptor.size != size || face.texture_descriptor.format != format
+    }) {
+        return None;
+    }
+
+    let data = faces
+        .iter()
+        .flat_map(|face| face.data.iter().copied())
+        .collect();
+    let mut image = Image::new(
+        Extent3d {
+            width: size.width,
+            height: size.height,
+            depth_or_array_layers: 6,
+        },
+        TextureDimension::D2,
+        data,
+        format,
+    );
+    image.texture_view_descriptor = Some(cube_view());
+    Some(image)
+}
+
+fn cube_view() -> TextureViewDescriptor<'static> {
+    TextureViewDescriptor {
+        dimension: Some(TextureViewDimension::Cube),
+        ..default()
+    }
+}
+
+/// world direction through a texel of a cube face, u and v from -1 to 1 with v down
+fn face_direction(face: u32, u: f32, v: f32) -> Vec3 {
+    // the cube texture is left handed, the skybox shader flips z when sampling
+    let direction = match face {
+        0 => Vec3::new(1.0, -v, -u),
+        1 => Vec3::new(-1.0, -v, u),
+        2 => Vec3::new(u, 1.0, v),
+        3 => Vec3::new(u, -1.0, -v),
+        4 => Vec3::new(u, -v, 1.0),
+        _ => Vec3::new(-u, -v, -1.0),
+    };
+    Vec3::new(direction.x, direction.y, -direction.z).normalize()
+}
+
+/// bilinear lookup of the panorama, its center faces -z
+fn sample_panorama(panorama: &Image, direction: Vec3) -> Vec4 {
+    let width = panorama.texture_descriptor.size.width;
+    let height = panorama.texture_descriptor.size.height;
+    let longitude = direction.x.atan2(-direction.z);
+    let latitude = direction.y.clamp(-1.0, 1.0).asin();
+    let x = (0.5 + longitude / (2.0 * PI)) * width as f32 - 0.5;
+    let y = (0.5 - latitude / PI) * height as f32 - 0.5;
+
+    let x0 = x.floor();
+    let y0 = y.floor();
+    let tx = x - x0;
+    let ty = y - y0;
+    // wrap around horizontally, clamp at the poles
+    let column = |x: f32| (x as i64).rem_euclid(width as i64) as u32;
+    let row = |y: f32| (y.max(0.0) as u32).min(height - 1);
+    let texel = |x: f32, y: f32| read_texel(panorama, column(x), row(y)).unwrap_or(Vec4::ZERO);
+
+    let top = texel(x0, y0).lerp(texel(x0 + 1.0, y0), tx);
+    let bottom = texel(x0, y0 + 1.0).lerp(texel(x0 + 1.0, y0 + 1.0), tx);
+    top.lerp(bottom, ty)
+}
+
+/// linear rgba of a texel, for the formats hdr, exr and png images load as
+fn read_texel(image: &Image, x: u32, y: u32) -> Option<Vec4> {
+    let index = (y * image.texture_descriptor.size.width + x) as usize;
+    match image.texture_descriptor.format {
+        TextureFormat::Rgba32Float => {
+            let bytes = image.data.get(index * 16..index * 16 + 16)?;
+            let mut channels = [0.0; 4];
+            for (channel, chunk) in channels.iter_mut().zip(bytes.chunks_exact(4)) {
+                *channel = f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+            }
+            Some(Vec4::from_array(channels))
+        }
+        TextureFormat::Rgba8UnormSrgb => {
+            let bytes = image.data.get(index * 4..index * 4 + 4)?;
+            let color = Color::rgba_u8(bytes[0], bytes[1], bytes[2], bytes[3]);
+            Some(Vec4::from_array(color.as_linear_rgba_f32()))
+        }
+        TextureFormat::Rgba8Unorm => {
+            let bytes = image.data.get(index * 4..index * 4 + 4)?;
+            Some(
+                Vec4::new(
+                    bytes[0] as f32,
+                    bytes[1] as f32,
+                    bytes[2] as f32,
+                    bytes[3] as f32,
+                ) / 255.0,
+            )
+        }
+        format => {
+            println!("Unsupported skybox format: {:?}", format);
+            None
+        }
+    }
+}
+
+/// half float bits rounded to nearest even, tiny values flush to zero,
+/// huge ones clamp to the largest half and NaN becomes zero so the sky never renders inf or NaN
+fn to_f16_bits(value: f32) -> u16 {
+    if value.is_nan() {
+        return 0;
+    }
+    // a bright sun in an hdri can go past the largest half
+    let bits = value.clamp(-F16_MAX, F16_MAX).to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent <= 0 {
+        return sign;
+    }
+    let half = ((exponent as u32) << 10) | (mantissa >> 13);
+    // a carry out of the mantissa moves into the exponent, the clamp keeps it below infinity
+    let dropped = mantissa & 0x1fff;
+    let round_up = dropped > 0x1000 || (dropped == 0x1000 && half & 1 == 1);
+    sign | (half + round_up as u32) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(width: u32, height: u32, format: TextureFormat, data: Vec<u8>) -> Image {
+        Image::new(
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            format,
+        )
+    }
+
+    /// red is the column and green the row of each texel
+    fn coordinate_panorama(width: u32, height: u32) -> Image {
+        let data = (0..height)
+            .flat_map(|y| (0..width).map(move |x| [x as f32, y as f32, 0.0, 1.0]))
+            .flatten()
+            .flat_map(f32::to_le_bytes)
+            .collect();
+        image(width, height, TextureFormat::Rgba32Float, data)
+    }
+
+    #[test]
+    fn f16_bits_of_exact_values() {
+        assert_eq!(to_f16_bits(0.0), 0x0000);
+        assert_eq!(to_f16_bits(1.0), 0x3c00);
+        assert_eq!(to_f16_bits(-2.0), 0xc000);
+        assert_eq!(to_f16_bits(0.5), 0x3800);
+        assert_eq!(to_f16_bits(F16_MAX), 0x7bff);
+    }
+
+    #[test]
+    fn f16_bits_never_inf_or_nan() {
+        assert_eq!(to_f16_bits(1.0e6), 0x7bff);
+        assert_eq!(to_f16_bits(f32::INFINITY), 0x7bff);
+        assert_eq!(to_f16_bits(f32::NEG_INFINITY), 0xfbff);
+        assert_eq!(to_f16_bits(f32::NAN), 0);
+        // just under the largest half must not round up into infinity
+        assert_eq!(to_f16_bits(65519.0), 0x7bff);
+    }
+
+    #[test]
+    fn f16_bits_round_to_nearest_even() {
+        let step = 2.0_f32.powi(-10);
+        // halfway ties go to the even mantissa
+        assert_eq!(to_f16_bits(1.0 + step * 0.5), 0x3c00);
+        assert_eq!(to_f16_bits(1.0 + step * 1.5), 0x3c02);
+        // past halfway rounds up, before it rounds down
+        assert_eq!(to_f16_bits(1.0 + step * 0.75), 0x3c01);
+        assert_eq!(to_f16_bits(1.0 + step * 0.25), 0x3c00);
+        // a carry out of the mantissa bumps the exponent
+        assert_eq!(to_f16_bits(2.0 - step * 0.25), 0x4000);
+    }
+
+    #[test]
+    fn face_centers_point_along_the_axes() {
+        // +z and -z swap because the skybox shader flips z
+        let expected = [
+            Vec3::X,
+            Vec3::NEG_X,
+            Vec3::Y,
+            Vec3::NEG_Y,
+            Vec3::NEG_Z,
+            Vec3::Z,
+        ];
+        for (face, axis) in expected.iter().enumerate() {
+            let direction = face_direction(face as u32, 0.0, 0.0);
+            assert!(
+                direction.abs_diff_eq(*axis, 1e-6),
+                "face {} points {:?}",
+                face,
+                direction
+            );
+        }
+    }
+
+    #[test]
+    fn panorama_center_faces_neg_z() {
+        let panorama = coordinate_panorama(8, 4);
+        let color = sample_panorama(&panorama, Vec3::NEG_Z);
+        assert!((color.x - 3.5).abs() < 1e-4);
+        assert!((color.y - 1.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn panorama_wraps_around_the_seam() {
+        let panorama = coordinate_panorama(8, 4);
+        // behind the center the last and first columns are blended
+        let color = sample_panorama(&panorama, Vec3::Z);
+        assert!((color.x - 3.5).abs() < 1e-4, "red {}", color.x);
+        assert!((color.y - 1.5).abs() < 1e-4, "green {}", color.y);
+    }
+
+    #[test]
+    fn panorama_clamps_at_the_poles() {
+        let panorama = coordinate_panorama(8, 4);
+        assert!(sample_panorama(&panorama, Vec3::Y).y.abs() < 1e-4);
+        assert!((sample_panorama(&panorama, Vec3::NEG_Y).y - 3.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn stack_faces_builds_a_cube() {
+        let faces: Vec<Image> = (0..6)
+            .map(|face| image(4, 4, TextureFormat::Rgba8Unorm, vec![face; 64]))
+            .collect();
+        let cube = stack_faces(&faces.iter().collect::<Vec<_>>()).unwrap();
+        assert_eq!(cube.texture_descriptor.size.depth_or_array_layers, 6);
+        assert_eq!(cube.data.len(), 64 * 6);
+        assert_eq!(cube.data[64 * 5], 5);
+    }
+
+    #[test]
+    fn stack_faces_rejects_mismatches() {
+        let face = || image(4, 4, TextureFormat::Rgba8Unorm, vec![0; 64]);
+        let mut faces: Vec<Image> = (0..6).map(|_| face()).collect();
+        faces[3] = image(2, 2, TextureFormat::Rgba8Unorm, vec![0; 16]);
+        assert!(stack_faces(&faces.iter().collect::<Vec<_>>()).is_none());
+
+        faces[3] = image(4, 4, TextureFormat::Rgba8UnormSrgb, vec![0; 64]);
+        assert!(stack_faces(&faces.iter().collect::<Vec<_>>()).is_none());
+
+        let wide: Vec<Image> = (0..6)
+            .map(|_| image(8, 4, TextureFormat::Rgba8Unorm, vec![0; 128]))
+            .collect();
+        assert!(stack_faces(&wide.iter().collect::<Vec<_>>()).is_none());
+
+        let five: Vec<Image> = (0..5).map(|_| face()).collect();
+        assert!(stack_faces(&five.iter().collect::<Vec<_>>()).is_none());
+    }
+}
@@ -0,0 +1,152 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::{Collider, GravityScale, RigidBody, Velocity as RigidBodyVelocity};
+use rand::prelude::*;
+
+use crate::events::{EffectEvent, EffectKind, TeardownLevelEvent};
+use crate::faction::debris_groups;
+use crate::rng::{GameRng, RngStream};
+
+const DEBRIS_LIFETIME: f32 = 3.0;
+/// debris fades out over the end of its lifetime
+const DEBRIS_FADE_TIME: f32 = 1.0;
+/// chunks per unit of wreck size
+const DEBRIS_PER_SIZE: f32 = 2.0;
+const DEBRIS_MIN_COUNT: usize = 3;
+const DEBRIS_MAX_COUNT: usize = 16;
+const DEBRIS_MIN_SPEED: f32 = 10.0;
+const DEBRIS_MAX_SPEED: f32 = 25.0;
+/// radians per second
+const DEBRIS_MAX_SPIN: f32 = 8.0;
+const DEBRIS_GRAVITY: f32 = 0.5;
+/// the glow of freshly torn metal, cools over the lifetime
+const DEBRIS_GLOW: Color = Color::rgb(2.0, 0.6, 0.1);
+
+pub struct DebrisPlugin;
+
+impl Plugin for DebrisPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(setup_debris_assets)
+            .add_system(spawn_debris)
+            .add_system(fade_debris)
+            .add_system(on_teardown);
+    }
+}
+
+/// A wreck chunk thrown from a destroyed ship, fades and despawns after a few seconds
+#[derive(Component)]
+pub struct Debris {
+    timer: Timer,
+}
+
+#[derive(Resource)]
+struct DebrisAssets {
+    mesh: Handle<Mesh>,
+}
+
+fn setup_debris_assets(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    commands.insert_resource(DebrisAssets {
+        mesh: meshes.add(Mesh::from(shape::Cube { size: 1.0 })),
+    });
+}
+
+fn spawn_debris(
+    mut commands: Commands,
+    debris_assets: Res<DebrisAssets>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut game_rng: ResMut<GameRng>,
+    mut ev_effect: EventReader<EffectEvent>,
+) {
+    for ev in ev_effect.iter() {
+        if ev.kind != EffectKind::Destruction {
+            continue;
+        }
+
+        let rng = game_rng.stream(RngStream::Effects);
+        let count = ((ev.size * DEBRIS_PER_SIZE).round() as usize)
+            .clamp(DEBRIS_MIN_COUNT, DEBRIS_MAX_COUNT);
+        for _ in 0..count {
+            let direction = Vec3::new(
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+            )
+            .normalize_or_zero();
+            let spin = Vec3::new(
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+            ) * DEBRIS_MAX_SPIN;
+            let speed = rng.gen_range(DEBRIS_MIN_SPEED..DEBRIS_MAX_SPEED);
+            let scale = Vec3::new(
+                rng.gen_range(0.2..0.6),
+                rng.gen_range(0.2..0.6),
+                rng.gen_range(0.2..0.6),
+            ) * ev.size.sqrt();
+
+            // every chunk fades on its own, so each gets its own material
+            let material = materials.add(StandardMaterial {
+                base_color: Color::rgb(0.3, 0.3, 0.32),
+                emissive: DEBRIS_GLOW,
+                alpha_mode: AlphaMode::Blend,
+                ..default()
+            });
+
+            commands.spawn((
+                PbrBundle {
+                    mesh: debris_assets.mesh.clone(),
+                    material,
+                    transform: Transform::from_translation(ev.position + direction * ev.size * 0.5)
+                        .with_scale(scale),
+                    ..default()
+                },
+                Debris {
+                    timer: Timer::from_seconds(DEBRIS_LIFETIME, TimerMode::Once),
+                },
+                RigidBody::Dynamic,
+                Collider::cuboid(0.5, 0.5, 0.5),
+                debris_groups(),
+                GravityScale(DEBRIS_GRAVITY),
+                RigidBodyVelocity {
+                    // thrown away from the wreck and a bit along the hit normal
+                    linvel: (direction + ev.normal * 0.5).normalize_or_zero() * speed,
+                    angvel: spin,
+                },
+            ));
+        }
+    }
+}
+
+/// cool down and fade out, then despawn
+fn fade_debris(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Debris, &Handle<StandardMaterial>)>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    time: Res<Time>,
+) {
+    for (entity, mut debris, material) in query.iter_mut() {
+        if debris.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        let Some(material) = materials.get_mut(material) else {
+            continue;
+        };
+        let heat = debris.timer.percent_left();
+        let alpha = (debris.timer.remaining_secs() / DEBRIS_FADE_TIME).min(1.0);
+        material.emissive = DEBRIS_GLOW * heat;
+        material.base_color.set_a(alpha);
+    }
+}
+
+fn on_teardown(
+    mut commands: Commands,
+    query: Query<Entity, With<Debris>>,
+    mut ev_teardown: EventReader<TeardownLevelEvent>,
+) {
+    for _ in ev_teardown.iter() {
+        for entity in query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use bevy::prelude::*;
+
+use crate::boss::Boss;
+use crate::bullet::{Beam, ProjectileKind};
+use crate::events::{DamageEvent, SpawnBulletEvent, TeardownLevelEvent};
+use crate::faction::Faction;
+use crate::health::Health;
+use crate::weapon::Weapon;
+
+/// seconds of play the adaptive mode looks at before adjusting
+const ADAPTIVE_WINDOW: f32 = 10.0;
+const ADAPTIVE_STEP: f32 = 0.1;
+const ADAPTIVE_MIN: f32 = 0.6;
+const ADAPTIVE_MAX: f32 = 1.5;
+
+pub struct DifficultyPlugin;
+
+impl Plugin for DifficultyPlugin {
+    fn build(&self, app: &mut App) {
+        let preset = preset_from_args().unwrap_or(DifficultyPreset::Normal);
+        let adaptive = adaptive_from_args();
+        println!("Difficulty: {:?}, adaptive: {}", preset, adaptive);
+        app.insert_resource(Difficulty::new(preset, adaptive))
+            .add_system(track_performance)
+            .add_system(scale_new_enemies)
+            .add_system(on_teardown);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DifficultyPreset {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl DifficultyPreset {
+    /// spawn density, enemy health and enemy fire rate multipliers
+    fn multipliers(&self) -> (f32, f32, f32) {
+        match self {
+            DifficultyPreset::Easy => (0.7, 0.7, 0.6),
+            DifficultyPreset::Normal => (1.0, 1.0, 1.0),
+            DifficultyPreset::Hard => (1.3, 1.5, 1.4),
+        }
+    }
+}
+
+/// How hard the game currently is, read by anything that spawns or arms enemies
+#[derive(Resource)]
+pub struct Difficulty {
+    pub preset: DifficultyPreset,
+    /// scale the preset by how well the player is doing
+    pub adaptive: bool,
+    /// adaptive multiplier applied on top of the preset, 1 when not adaptive
+    pub scale: f32,
+    performance: Performance,
+}
+
+/// player performance over the current adaptive window
+#[derive(Default)]
+struct Performance {
+    elapsed: f32,
+    /// damage the player's shots would deal if every one of them hit
+    damage_fired: f32,
+    damage_hit: f32,
+    damage_taken: f32,
+}
+
+impl Difficulty {
+    pub fn new(preset: DifficultyPreset, adaptive: bool) -> Self {
+        Self {
+            preset,
+            adaptive,
+            scale: 1.0,
+            performance: Performance::default(),
+        }
+    }
+
+    /// multiplier for the number of enemies per wave and how often waves come
+    pub fn spawn_density(&self) -> f32 {
+        self.preset.multipliers().0 * self.scale
+    }
+
+    pub fn enemy_health(&self) -> f32 {
+        self.preset.multipliers().1 * self.scale
+    }
+
+    pub fn enemy_fire_rate(&self) -> f32 {
+        self.preset.multipliers().2 * self.scale
+    }
+}
+
+/// read the preset from `--difficulty <easy|normal|hard>` or the GAME_DIFFICULTY environment variable
+fn preset_from_args() -> Option<DifficultyPreset> {
+    let args: Vec<String> = std::env::args().collect();
+    let name = args
+        .iter()
+        .position(|arg| arg == "--difficulty")
+        .and_then(|index| args.get(index + 1).cloned())
+        .or_else(|| std::env::var("GAME_DIFFICULTY").ok())?;
+    match name.to_lowercase().as_str() {
+        "easy" => Some(DifficultyPreset::Easy),
+        "normal" => Some(DifficultyPreset::Normal),
+        "hard" => Some(DifficultyPreset::Hard),
+        _ => {
+            println!("Unknown difficulty: {}", name);
+            None
+        }
+    }
+}
+
+/// `--adaptive` or GAME_ADAPTIVE=1
+fn adaptive_from_args() -> bool {
+    std::env::args().any(|arg| arg == "--adaptive")
+        || std::env::var("GAME_ADAPTIVE").is_ok_and(|value| value == "1")
+}
+
+/// weigh shots and hits by damage, then nudge the adaptive scale at the end of every window
+fn track_performance(
+    mut difficulty: ResMut<Difficulty>,
+    beam_query: Query<&Beam>,
+    mut ev_fire: EventReader<SpawnBulletEvent>,
+    mut ev_damage: EventReader<DamageEvent>,
+    time: Res<Time>,
+) {
+    // a spread hits with several bolts and a beam every frame, so counting events overshoots
+    for ev in ev_fire.iter() {
+        if ev.faction != Faction::Player {
+            continue;
+        }
+        difficulty.performance.damage_fired += match ev.kind {
+            ProjectileKind::Spread { count, .. } => ev.damage * count as f32,
+            // counted below for as long as the beam is on
+            ProjectileKind::Beam { .. } => 0.0,
+            _ => ev.damage,
+        };
+    }
+    for beam in beam_query.iter() {
+        if beam.faction == Faction::Player {
+            difficulty.performance.damage_fired += beam.damage_per_second * time.delta_seconds();
+        }
+    }
+    for ev in ev_damage.iter() {
+        match ev.source {
+            Faction::Player => difficulty.performance.damage_hit += ev.amount,
+            Faction::Enemy => difficulty.performance.damage_taken += ev.amount,
+        }
+    }
+
+    if !difficulty.adaptive {
+        return;
+    }
+    difficulty.performance.elapsed += time.delta_seconds();
+    if difficulty.performance.elapsed < ADAPTIVE_WINDOW {
+        return;
+    }
+
+    let performance = std::mem::take(&mut difficulty.performance);
+    let hit_ratio = if performance.damage_fired > 0.0 {
+        (performance.damage_hit / performance.damage_fired).min(1.0)
+    } else {
+        0.0
+    };
+    // harder when accurate and untouched, easier when taking damage or missing everything
+    let step =
+        if performance.damage_taken >= 3.0 || (performance.damage_fired > 0.0 && hit_ratio < 0.1) {
+            -ADAPTIVE_STEP
+        } else if performance.damage_taken == 0.0 && hit_ratio >= 0.3 {
+            ADAPTIVE_STEP
+        } else {
+            0.0
+        };
+    if step != 0.0 {
+        difficulty.scale = (difficulty.scale + step).clamp(ADAPTIVE_MIN, ADAPTIVE_MAX);
+        debug!(
+            "Adaptive difficulty: {:.1} (hit ratio {:.2}, damage taken {})",
+            difficulty.scale, hit_ratio, performance.damage_taken
+        );
+    }
+}
+
+/// the stats difficulty scales on a newly spawned ship
+type ScaledStats<'a> = (
+    &'a Faction,
+    Option<&'a mut Health>,
+    Option<&'a mut Weapon>,
+    Option<&'a mut Boss>,
+);
+
+/// scale the health and fire rate of enemies as they spawn
+fn scale_new_enemies(
+    mut query: Query<ScaledStats<'static>, Added<Faction>>,
+    difficulty: Res<Difficulty>,
+) {
+    for (faction, health, weapon, boss) in query.iter_mut() {
+        if *faction != Faction::Enemy {
+            continue;
+        }
+        if let Some(mut health) = health {
+            health.max *= difficulty.enemy_health();
+            health.current = health.max;
+        }
+        if let Some(mut weapon) = weapon {
+            weapon.fire_rate *= difficulty.enemy_fire_rate();
+        }
+        // boss parts are scaled as they spawn, keep the phase thresholds in step
+        if let Some(mut boss) = boss {
+            boss.max_health *= difficulty.enemy_health();
+        }
+    }
+}
+
+// the adaptive scale carries over to the next level, only the window starts over
+fn on_teardown(
+    mut difficulty: ResMut<Difficulty>,
+    mut ev_teardown: EventReader<TeardownLevelEvent>,
+) {
+    for _ in ev_teardown.iter() {
+        difficulty.performance = Performance::default();
+    }
+}
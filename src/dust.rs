@@ -0,0 +1,184 @@
+use bevy::pbr::NotShadowCaster;
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::render::view::NoFrustumCulling;
+use rand::prelude::*;
+
+use crate::player::{Boost, Player, PlayerCamera};
+use crate::rng::{GameRng, RngStream};
+use crate::velocity::MeasuredVelocity;
+
+const DUST_COUNT: usize = 400;
+/// half size of the box of dust kept around the camera
+const DUST_FIELD_EXTENT: f32 = 60.0;
+const DUST_SIZE: f32 = 0.06;
+/// extra length per unit of speed
+const DUST_STRETCH: f32 = 0.05;
+/// lines are this much longer while boosting
+const DUST_BOOST_STRETCH: f32 = 2.0;
+
+/// corners of a mote, unit length along z, stretched and turned with the motion every frame
+const MOTE_CORNERS: [Vec3; 8] = [
+    Vec3::new(-0.5, -0.5, -0.5),
+    Vec3::new(0.5, -0.5, -0.5),
+    Vec3::new(0.5, 0.5, -0.5),
+    Vec3::new(-0.5, 0.5, -0.5),
+    Vec3::new(-0.5, -0.5, 0.5),
+    Vec3::new(0.5, -0.5, 0.5),
+    Vec3::new(0.5, 0.5, 0.5),
+    Vec3::new(-0.5, 0.5, 0.5),
+];
+const MOTE_INDICES: [u32; 36] = [
+    0, 2, 1, 0, 3, 2, // back
+    4, 5, 6, 4, 6, 7, // front
+    0, 1, 5, 0, 5, 4, // bottom
+    3, 6, 2, 3, 7, 6, // top
+    0, 4, 7, 0, 7, 3, // left
+    1, 2, 6, 1, 6, 5, // right
+];
+
+pub struct DustPlugin;
+
+impl Plugin for DustPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(spawn_dust).add_system(update_dust);
+    }
+}
+
+/// Space dust around a player camera, every mote is part of one mesh so the field is a single draw.
+/// Motes that leave the box wrap back in on the other side and stretch into speed lines when moving
+#[derive(Component)]
+pub struct Dust {
+    camera: Entity,
+    /// world positions
+    motes: Vec<Vec3>,
+    mesh: Handle<Mesh>,
+}
+
+/// a player camera that already has its dust
+#[derive(Component)]
+struct DustAttached;
+
+/// player cameras still waiting for their dust
+type CameraWithoutDust = (With<PlayerCamera>, Without<DustAttached>);
+
+fn spawn_dust(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut game_rng: ResMut<GameRng>,
+    camera_query: Query<(Entity, &GlobalTransform), CameraWithoutDust>,
+) {
+    for (camera, camera_transform) in camera_query.iter() {
+        let center = camera_transform.translation();
+        let rng = game_rng.stream(RngStream::Effects);
+        let motes: Vec<Vec3> = (0..DUST_COUNT)
+            .map(|_| {
+                center
+                    + Vec3::new(
+                        rng.gen_range(-DUST_FIELD_EXTENT..DUST_FIELD_EXTENT),
+                        rng.gen_range(-DUST_FIELD_EXTENT..DUST_FIELD_EXTENT),
+                        rng.gen_range(-DUST_FIELD_EXTENT..DUST_FIELD_EXTENT),
+                    )
+            })
+            .collect();
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            vec![[0.0, 0.0, 0.0]; DUST_COUNT * MOTE_CORNERS.len()],
+        );
+        // unlit, the normals are only there for the pipeline
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_NORMAL,
+            vec![[0.0, 0.0, 1.0]; DUST_COUNT * MOTE_CORNERS.len()],
+        );
+        mesh.set_indices(Some(Indices::U32(
+            (0..DUST_COUNT as u32)
+                .flat_map(|mote| {
+                    MOTE_INDICES
+                        .iter()
+                        .map(move |index| mote * MOTE_CORNERS.len() as u32 + index)
+                })
+                .collect(),
+        )));
+        let mesh = meshes.add(mesh);
+
+        commands.entity(camera).insert(DustAttached);
+        commands.spawn((
+            PbrBundle {
+                mesh: mesh.clone(),
+                material: materials.add(StandardMaterial {
+                    base_color: Color::rgba(0.8, 0.85, 1.0, 0.6),
+                    unlit: true,
+                    alpha_mode: AlphaMode::Add,
+                    cull_mode: None,
+                    ..default()
+                }),
+                transform: Transform::from_translation(center),
+                ..default()
+            },
+            Dust {
+                camera,
+                motes,
+                mesh,
+            },
+            NotShadowCaster,
+            // the mesh is rebuilt around the camera every frame, its bounds are never up to date
+            NoFrustumCulling,
+        ));
+    }
+}
+
+/// wrap motes that left the box back in on the other side, then line them up with the motion
+fn update_dust(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut dust_query: Query<(Entity, &mut Dust, &mut Transform)>,
+    camera_query: Query<&GlobalTransform, With<PlayerCamera>>,
+    player_query: Query<(&MeasuredVelocity, &Boost), With<Player>>,
+) {
+    let (velocity, boosting) = match player_query.iter().next() {
+        Some((measured, boost)) => (measured.value, boost.active),
+        None => (Vec3::ZERO, false),
+    };
+    let speed = velocity.length();
+    let mut length = 1.0 + speed * DUST_STRETCH;
+    if boosting {
+        length *= DUST_BOOST_STRETCH;
+    }
+    let rotation = match velocity.try_normalize() {
+        Some(direction) => Quat::from_rotation_arc(Vec3::NEG_Z, direction),
+        None => Quat::IDENTITY,
+    };
+    let scale = Vec3::new(DUST_SIZE, DUST_SIZE, DUST_SIZE * length);
+    // every mote has the same shape, only where it sits differs
+    let corners = MOTE_CORNERS.map(|corner| rotation * (corner * scale));
+
+    let size = DUST_FIELD_EXTENT * 2.0;
+    for (entity, mut dust, mut transform) in dust_query.iter_mut() {
+        // the field goes with the camera, ex: when the player respawns
+        let Ok(camera_transform) = camera_query.get(dust.camera) else {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+        let center = camera_transform.translation();
+        transform.translation = center;
+
+        let Some(mesh) = meshes.get_mut(&dust.mesh) else {
+            continue;
+        };
+        let mut positions = Vec::with_capacity(dust.motes.len() * corners.len());
+        for mote in dust.motes.iter_mut() {
+            let offset = *mote - center + DUST_FIELD_EXTENT;
+            let wrapped = Vec3::new(
+                offset.x.rem_euclid(size),
+                offset.y.rem_euclid(size),
+                offset.z.rem_euclid(size),
+            ) - DUST_FIELD_EXTENT;
+            *mote = center + wrapped;
+            positions.extend(corners.iter().map(|corner| (wrapped + *corner).to_array()));
+        }
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    }
+}
@@ -0,0 +1,199 @@
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use bevy_hanabi::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Every particle effect by name, loaded from a .effects.ron file
+#[derive(Deserialize, TypeUuid, Debug)]
+#[uuid = "9d2e4b7a-1f63-4c85-a0d9-5e8b2c6f4a13"]
+pub struct EffectLibrary {
+    pub effects: HashMap<String, EffectDefinition>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct EffectDefinition {
+    /// max particles alive per instance
+    pub capacity: u32,
+    pub spawner: SpawnerDefinition,
+    /// seconds an instance plays before it is reused, longer than the longest particle lifetime
+    pub duration: f32,
+    /// instances playing at the same time, the oldest one is cut short past this
+    pub max_instances: usize,
+    pub position: ShapeDefinition,
+    pub velocity: ShapeDefinition,
+    pub lifetime: ValueDefinition,
+    #[serde(default)]
+    pub age: Option<ValueDefinition>,
+    #[serde(default)]
+    pub drag: Option<f32>,
+    #[serde(default)]
+    pub accel: Option<(f32, f32, f32)>,
+    /// (time, (r, g, b, a)) keys over the particle lifetime
+    pub color: Vec<(f32, (f32, f32, f32, f32))>,
+    /// (time, (width, height)) keys over the particle lifetime
+    pub size: Vec<(f32, (f32, f32))>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub enum ValueDefinition {
+    Single(f32),
+    Uniform(f32, f32),
+}
+
+impl From<ValueDefinition> for Value<f32> {
+    fn from(value: ValueDefinition) -> Self {
+        match value {
+            ValueDefinition::Single(value) => Value::Single(value),
+            ValueDefinition::Uniform(min, max) => Value::Uniform((min, max)),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub enum SpawnerDefinition {
+    /// a single burst when the instance starts
+    Once { count: ValueDefinition },
+    /// continuous spawning, ex: trails
+    Rate { per_second: ValueDefinition },
+    /// a burst every period seconds
+    Burst {
+        count: ValueDefinition,
+        period: ValueDefinition,
+    },
+}
+
+/// where particles start and which way they move, relative to the effect transform
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub enum ShapeDefinition {
+    Sphere {
+        radius: f32,
+        #[serde(default)]
+        volume: bool,
+        /// only used for velocity
+        #[serde(default)]
+        speed: Option<ValueDefinition>,
+    },
+    /// a circle around the local z axis
+    Circle {
+        radius: f32,
+        #[serde(default)]
+        volume: bool,
+        #[serde(default)]
+        speed: Option<ValueDefinition>,
+    },
+}
+
+impl ShapeDefinition {
+    fn dimension(volume: bool) -> ShapeDimension {
+        if volume {
+            ShapeDimension::Volume
+        } else {
+            ShapeDimension::Surface
+        }
+    }
+
+    fn speed(speed: Option<ValueDefinition>) -> Value<f32> {
+        speed.map_or(Value::Single(0.0), Value::from)
+    }
+}
+
+impl EffectDefinition {
+    pub fn build(&self, name: &str) -> EffectAsset {
+        let spawner = match self.spawner {
+            SpawnerDefinition::Once { count } => Spawner::once(count.into(), true),
+            SpawnerDefinition::Rate { per_second } => Spawner::rate(per_second.into()),
+            SpawnerDefinition::Burst { count, period } => {
+                Spawner::burst(count.into(), period.into())
+            }
+        };
+
+        let mut color_gradient = Gradient::new();
+        for (time, (r, g, b, a)) in self.color.iter() {
+            color_gradient.add_key(*time, Vec4::new(*r, *g, *b, *a));
+        }
+        let mut size_gradient = Gradient::new();
+        for (time, (width, height)) in self.size.iter() {
+            size_gradient.add_key(*time, Vec2::new(*width, *height));
+        }
+
+        let mut effect = EffectAsset {
+            name: name.to_string(),
+            capacity: self.capacity,
+            spawner,
+            ..Default::default()
+        };
+
+        effect = match self.position {
+            ShapeDefinition::Sphere { radius, volume, .. } => {
+                effect.init(InitPositionSphereModifier {
+                    center: Vec3::ZERO,
+                    radius,
+                    dimension: ShapeDefinition::dimension(volume),
+                })
+            }
+            ShapeDefinition::Circle { radius, volume, .. } => {
+                effect.init(InitPositionCircleModifier {
+                    center: Vec3::ZERO,
+                    axis: Vec3::Z,
+                    radius,
+                    dimension: ShapeDefinition::dimension(volume),
+                })
+            }
+        };
+        effect = match self.velocity {
+            ShapeDefinition::Sphere { speed, .. } => effect.init(InitVelocitySphereModifier {
+                center: Vec3::ZERO,
+                speed: ShapeDefinition::speed(speed),
+            }),
+            ShapeDefinition::Circle { speed, .. } => effect.init(InitVelocityCircleModifier {
+                center: Vec3::ZERO,
+                axis: Vec3::Z,
+                speed: ShapeDefinition::speed(speed),
+            }),
+        };
+        effect = effect.init(InitLifetimeModifier {
+            lifetime: self.lifetime.into(),
+        });
+        if let Some(age) = self.age {
+            effect = effect.init(InitAgeModifier { age: age.into() });
+        }
+        if let Some(drag) = self.drag {
+            effect = effect.update(LinearDragModifier { drag });
+        }
+        if let Some((x, y, z)) = self.accel {
+            effect = effect.update(AccelModifier::constant(Vec3::new(x, y, z)));
+        }
+
+        effect
+            .render(ColorOverLifetimeModifier {
+                gradient: color_gradient,
+            })
+            .render(SizeOverLifetimeModifier {
+                gradient: size_gradient,
+            })
+    }
+}
+
+#[derive(Default)]
+pub struct EffectLibraryLoader;
+
+impl AssetLoader for EffectLibraryLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let library = ron::de::from_bytes::<EffectLibrary>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(library));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["effects.ron"]
+    }
+}
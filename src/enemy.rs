@@ -1,37 +1,49 @@
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
-use rand::prelude::*;
-use std::time::Duration;
 
+use crate::behaviour::{Behaviour, MovementPattern};
 use crate::constants::BOUNDS_POS;
+use crate::enemy_registry::EnemyArchetype;
+use crate::enemy_weapon::FirePattern;
 use crate::events::TeardownLevelEvent;
+use crate::faction::Faction;
+use crate::formation::{Formation, Squadron, SquadronMember};
+use crate::health::Health;
+use crate::level::PlayerPath;
 use crate::math::deg_to_rad;
+use crate::pool::{EntityPools, PoolCommandsExt};
+use crate::rail::{PlayerRail, RailSpace};
+use crate::thruster::ThrusterMount;
 use crate::velocity::Velocity;
+use crate::weapon::Weapon;
 
-const ENEMY_SPEED: f32 = 100.0;
-const ENEMY_SPAWN_TIME: u64 = 1;
+const ENEMY_SPAWN_DISTANCE: f32 = BOUNDS_POS.z - 1.0;
+const SQUADRON_SPACING: f32 = 8.0;
 
 pub struct EnemyPlugin;
 
 impl Plugin for EnemyPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(setup)
-            .add_system(spawn_enemies)
-            .add_system(on_teardown);
+        app.add_system(on_teardown);
     }
 }
 
 #[derive(Component)]
 struct Enemy;
-#[derive(Resource)]
-struct EnemySpawnTime {
-    timer: Timer,
-}
 
-fn setup(mut commands: Commands) {
-    commands.insert_resource(EnemySpawnTime {
-        timer: Timer::new(Duration::from_secs(ENEMY_SPAWN_TIME), TimerMode::Repeating),
-    })
+/// points awarded for destroying this enemy
+#[derive(Component)]
+pub struct ScoreValue(pub u32);
+
+/// Everything needed to spawn a squadron of enemies ahead of the player
+pub struct SquadronSpawn<'a> {
+    pub archetype: &'a EnemyArchetype,
+    pub formation: Formation,
+    pub pattern: MovementPattern,
+    pub fire_pattern: FirePattern,
+    pub count: usize,
+    /// lateral and vertical offset from the rail
+    pub offset: Vec2,
 }
 
 fn on_teardown(
@@ -41,41 +53,102 @@ fn on_teardown(
 ) {
     for _ in ev_teardown.iter() {
         for entity in query.iter() {
-            commands.entity(entity).despawn_recursive();
+            commands.despawn_pooled(entity);
         }
     }
 }
 
-fn spawn_enemies(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    time: Res<Time>,
-    mut spawn_timer: ResMut<EnemySpawnTime>,
-) {
-    spawn_timer.timer.tick(time.delta());
-
-    let mut rng = rand::thread_rng();
+/// spawn a squadron ahead of the player on the rail, returns the squadron entity
+pub fn spawn_squadron(
+    commands: &mut Commands,
+    pools: &mut EntityPools,
+    asset_server: &AssetServer,
+    rail: &PlayerRail,
+    player_path: Option<&PlayerPath>,
+    spawn: &SquadronSpawn,
+) -> Entity {
+    let archetype = spawn.archetype;
+    // facing back towards the player
+    let rail_spawn = RailSpace::new(
+        rail.distance + ENEMY_SPAWN_DISTANCE,
+        spawn.offset,
+        Quat::from_rotation_y(deg_to_rad(180.0)),
+    );
+    // paths are in world space, everything else moves relative to the rail
+    let in_rail_space = !matches!(spawn.pattern, MovementPattern::FollowPath { .. });
 
-    let x_spawn: f32 = rng.gen_range(-BOUNDS_POS.x..BOUNDS_POS.x);
-    let y_spawn: f32 = rng.gen_range(-BOUNDS_POS.y..BOUNDS_POS.y);
-    let vec_spawn = Vec3::new(x_spawn, y_spawn, -BOUNDS_POS.z + 1.0);
+    // every member shares the same pattern and frame so the squadron moves as one
+    let squadron = commands.spawn_empty().id();
+    let members: Vec<Entity> = spawn
+        .formation
+        .offsets(spawn.count, SQUADRON_SPACING)
+        .iter()
+        .map(|offset| {
+            let mut member_rail = rail_spawn;
+            member_rail.translate(rail_spawn.rotation * *offset);
+            let transform = member_rail.to_world(rail, player_path);
 
-    let mut transform_spawn = Transform::from_translation(vec_spawn);
-    transform_spawn.rotate_y(deg_to_rad(180.0));
+            let frame = if in_rail_space {
+                rail_spawn.rotation
+            } else {
+                transform.rotation
+            };
+            let entity = spawn_enemy(
+                commands,
+                pools,
+                asset_server,
+                archetype,
+                transform,
+                Behaviour::new(spawn.pattern.clone(), archetype.speed, frame).with_offset(*offset),
+                spawn.fire_pattern.clone(),
+            );
+            commands.entity(entity).insert(SquadronMember(squadron));
+            if in_rail_space {
+                commands.entity(entity).insert(member_rail);
+            }
+            entity
+        })
+        .collect();
+    // clearing the whole squadron scores every member a second time
+    commands
+        .entity(squadron)
+        .insert(Squadron::new(members, archetype.score * spawn.count as u32));
+    squadron
+}
 
-    if spawn_timer.timer.finished() {
-        commands.spawn((
-            SceneBundle {
-                scene: asset_server.load("models/Spaceship/enemy.gltf#Scene0"),
-                transform: transform_spawn,
-                ..default()
-            },
+pub fn spawn_enemy(
+    commands: &mut Commands,
+    pools: &mut EntityPools,
+    asset_server: &AssetServer,
+    archetype: &EnemyArchetype,
+    transform: Transform,
+    behaviour: Behaviour,
+    fire_pattern: FirePattern,
+) -> Entity {
+    let entity = commands
+        .spawn((
+            SpatialBundle::from_transform(transform),
             Enemy,
-            Velocity(Vec3::Z * ENEMY_SPEED),
-            Collider::cuboid(2.17, 1.45, 1.73),
+            Velocity(behaviour.frame * Vec3::NEG_Z * behaviour.speed),
+            behaviour,
+            Faction::Enemy,
+            Health::new(archetype.health),
+            ScoreValue(archetype.score),
+            Weapon::new(archetype.weapon.fire_rate, 0.0, 0.0, 0.0),
+            fire_pattern,
+            archetype.weapon.projectile,
+            archetype.collider.to_collider(),
+            Faction::Enemy.body_groups(),
             RigidBody::Dynamic,
             GravityScale(0.0),
             ActiveEvents::COLLISION_EVENTS,
-        ));
+        ))
+        .id();
+    if let Some((x, y, z)) = archetype.thruster {
+        commands
+            .entity(entity)
+            .insert(ThrusterMount::new(Vec3::new(x, y, z)));
     }
+    pools.attach_model(commands, asset_server, entity, &archetype.model);
+    entity
 }
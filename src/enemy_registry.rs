@@ -0,0 +1,134 @@
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use bevy_asset_loader::prelude::*;
+use bevy_rapier3d::prelude::Collider;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::behaviour::MovementPattern;
+use crate::boss::{BossPartDescriptor, BossPhase};
+use crate::bullet::ProjectileKind;
+use crate::enemy_weapon::FirePattern;
+
+pub struct EnemyRegistryPlugin;
+
+impl Plugin for EnemyRegistryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<EnemyRegistry>()
+            .init_asset_loader::<EnemyRegistryLoader>();
+    }
+}
+
+/// loaded with the level assets, see LevelPlugin
+#[derive(AssetCollection, Resource)]
+pub struct EnemyAssets {
+    #[asset(path = "data/enemy_types.enemies.ron")]
+    pub registry: Handle<EnemyRegistry>,
+}
+
+/// Every enemy type by name, loaded from a .enemies.ron file
+#[derive(Deserialize, TypeUuid, Debug)]
+#[uuid = "c4a7e0d2-5b1f-4e39-8f6a-2d9b3e7a1c58"]
+pub struct EnemyRegistry {
+    pub types: HashMap<String, EnemyArchetype>,
+    /// spawned by boss arenas instead of waves
+    #[serde(default)]
+    pub bosses: HashMap<String, BossArchetype>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct EnemyArchetype {
+    /// scene path, ex: "models/Spaceship/enemy.gltf#Scene0"
+    pub model: String,
+    pub collider: ColliderShape,
+    pub health: f32,
+    pub score: u32,
+    pub speed: f32,
+    /// used when a wave does not override the movement
+    pub movement: MovementPattern,
+    pub weapon: WeaponDescriptor,
+    /// thruster position relative to the ship, no thruster trail when missing
+    #[serde(default)]
+    pub thruster: Option<(f32, f32, f32)>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct BossArchetype {
+    /// scene path, the parts are placed over it
+    pub model: String,
+    #[serde(default = "default_model_scale")]
+    pub model_scale: f32,
+    pub parts: Vec<BossPartDescriptor>,
+    /// ordered by health_fraction from 1 down
+    pub phases: Vec<BossPhase>,
+}
+
+fn default_model_scale() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct WeaponDescriptor {
+    pub fire_rate: f32,
+    /// used when a wave does not override the fire pattern
+    pub pattern: FirePattern,
+    #[serde(default)]
+    pub projectile: ProjectileKind,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub enum ColliderShape {
+    Cuboid {
+        half_x: f32,
+        half_y: f32,
+        half_z: f32,
+    },
+    Ball {
+        radius: f32,
+    },
+    /// capsule along the forward axis
+    Capsule {
+        half_length: f32,
+        radius: f32,
+    },
+}
+
+impl ColliderShape {
+    pub fn to_collider(self) -> Collider {
+        match self {
+            ColliderShape::Cuboid {
+                half_x,
+                half_y,
+                half_z,
+            } => Collider::cuboid(half_x, half_y, half_z),
+            ColliderShape::Ball { radius } => Collider::ball(radius),
+            ColliderShape::Capsule {
+                half_length,
+                radius,
+            } => Collider::capsule_z(half_length, radius),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct EnemyRegistryLoader;
+
+impl AssetLoader for EnemyRegistryLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let registry = ron::de::from_bytes::<EnemyRegistry>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(registry));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["enemies.ron"]
+    }
+}
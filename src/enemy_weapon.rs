@@ -0,0 +1,134 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::bullet::ProjectileKind;
+use crate::events::SpawnBulletEvent;
+use crate::faction::Faction;
+use crate::math::{deg_to_rad, lead_target};
+use crate::player::Player;
+use crate::velocity::MeasuredVelocity;
+use crate::weapon::Weapon;
+
+const ENEMY_BULLET_SPEED: f32 = 120.0;
+const ENEMY_BULLET_DAMAGE: f32 = 1.0;
+const ENEMY_FIRE_RANGE: f32 = 200.0;
+
+pub struct EnemyWeaponPlugin;
+
+impl Plugin for EnemyWeaponPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(fire_at_player);
+    }
+}
+
+/// How an enemy fires its weapon at the player
+#[derive(Component, Clone, Debug, Deserialize)]
+pub enum FirePattern {
+    /// a single shot at the player, optionally leading the player's movement
+    Aimed { lead: bool },
+    /// a fan of shots spread horizontally around the aim direction, angle in degrees
+    Spread { count: u32, angle: f32 },
+    /// a ring of shots around the aim direction, angle is the cone half angle in degrees
+    Radial { count: u32, angle: f32 },
+}
+
+impl FirePattern {
+    /// get the direction of every shot fired for one trigger pull
+    pub fn directions(&self, aim: Vec3) -> Vec<Vec3> {
+        // build a basis around the aim direction
+        let right = aim.cross(Vec3::Y).try_normalize().unwrap_or(Vec3::X);
+        let up = right.cross(aim).normalize_or_zero();
+
+        match *self {
+            FirePattern::Aimed { .. } => vec![aim],
+            FirePattern::Spread { count, angle } => {
+                if count <= 1 {
+                    return vec![aim];
+                }
+                let step = deg_to_rad(angle) / (count - 1) as f32;
+                let start = -deg_to_rad(angle) / 2.0;
+                (0..count)
+                    .map(|i| Quat::from_axis_angle(up, start + step * i as f32) * aim)
+                    .collect()
+            }
+            FirePattern::Radial { count, angle } => {
+                let tilt = deg_to_rad(angle);
+                (0..count.max(1))
+                    .map(|i| {
+                        let around = std::f32::consts::TAU * i as f32 / count.max(1) as f32;
+                        let axis = Quat::from_axis_angle(aim, around) * right;
+                        Quat::from_axis_angle(axis, tilt) * aim
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Whether a shooter can currently see the player, shooters without it always can
+#[derive(Component, Default)]
+pub struct LineOfSight {
+    pub visible: bool,
+}
+
+/// an enemy weapon with what it fires and whether it can see the player
+type ArmedEnemy<'a> = (
+    Entity,
+    &'a GlobalTransform,
+    &'a mut Weapon,
+    &'a FirePattern,
+    &'a Faction,
+    Option<&'a ProjectileKind>,
+    Option<&'a LineOfSight>,
+);
+
+/// fire every patterned weapon that is ready and in range of the player
+fn fire_at_player(
+    mut query: Query<ArmedEnemy<'static>>,
+    player_query: Query<(&GlobalTransform, Option<&MeasuredVelocity>), With<Player>>,
+    mut ev_fire: EventWriter<SpawnBulletEvent>,
+) {
+    let Some((player_transform, player_velocity)) = player_query.iter().next() else {
+        return;
+    };
+    let player_position = player_transform.translation();
+    let player_velocity = player_velocity.map(|v| v.value).unwrap_or(Vec3::ZERO);
+
+    for (entity, global_transform, mut weapon, pattern, faction, kind, line_of_sight) in
+        query.iter_mut()
+    {
+        if line_of_sight.is_some_and(|los| !los.visible) {
+            continue;
+        }
+        let position = global_transform.translation();
+        if position.distance(player_position) > ENEMY_FIRE_RANGE || !weapon.try_fire() {
+            continue;
+        }
+
+        let target = match pattern {
+            FirePattern::Aimed { lead: true } => lead_target(
+                position,
+                player_position,
+                player_velocity,
+                ENEMY_BULLET_SPEED,
+            ),
+            _ => player_position,
+        };
+        let Some(aim) = (target - position).try_normalize() else {
+            continue;
+        };
+
+        for direction in pattern.directions(aim) {
+            ev_fire.send(SpawnBulletEvent {
+                kind: kind.copied().unwrap_or_default(),
+                shooter: entity,
+                transform: Transform::from_translation(position + direction * 3.0)
+                    .looking_at(position + direction * 4.0, Vec3::Y),
+                direction,
+                speed: ENEMY_BULLET_SPEED,
+                faction: *faction,
+                damage: ENEMY_BULLET_DAMAGE,
+            });
+        }
+    }
+}
@@ -0,0 +1,298 @@
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::pbr::{FogFalloff, FogSettings};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::{BoxedFuture, HashMap};
+use serde::Deserialize;
+
+use crate::cubemap::CubemapLayout;
+use crate::events::TeardownLevelEvent;
+use crate::level::ChangeLevelSet;
+use crate::skybox::{set_skybox_faces, set_skybox_images, set_skybox_path, Cubemap};
+
+pub struct EnvironmentPlugin;
+
+impl Plugin for EnvironmentPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<LevelEnvironment>()
+            .init_asset_loader::<LevelEnvironmentLoader>()
+            .init_resource::<EnvironmentState>()
+            // the new level's environment is inserted by commands, so tear down in the same
+            // frame as the level change but after the old environment was last checked
+            .add_system(apply_environment.after(ChangeLevelSet))
+            .add_system(on_teardown.after(apply_environment))
+            .add_system(apply_fog.after(on_teardown))
+            .add_system(cache_skybox.after(on_teardown));
+    }
+}
+
+/// Sky and lighting of a level, loaded from a .environment.ron file
+#[derive(Deserialize, TypeUuid, Debug, Clone)]
+#[uuid = "c4a1e6f2-7b39-4d08-9e5a-2f6b8d1c3a75"]
+pub struct LevelEnvironment {
+    pub skybox: SkyboxDescriptor,
+    /// skybox brightness in stops
+    #[serde(default)]
+    pub exposure: f32,
+    pub ambient: AmbientDescriptor,
+    #[serde(default)]
+    pub sun: Option<SunDescriptor>,
+    #[serde(default)]
+    pub fog: Option<FogDescriptor>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SkyboxDescriptor {
+    /// a stacked cubemap or an .hdr / .exr panorama
+    Image(String),
+    /// six images in the order +x, -x, +y, -y, +z, -z
+    Faces([String; 6]),
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct AmbientDescriptor {
+    pub color: (f32, f32, f32),
+    pub brightness: f32,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct SunDescriptor {
+    /// the way the light shines
+    pub direction: (f32, f32, f32),
+    pub color: (f32, f32, f32),
+    /// lux
+    pub illuminance: f32,
+    #[serde(default)]
+    pub shadows: bool,
+}
+
+/// linear fog between two distances from the camera
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct FogDescriptor {
+    pub color: (f32, f32, f32, f32),
+    pub start: f32,
+    pub end: f32,
+}
+
+/// The environment the current level asks for, applied once loaded
+#[derive(Resource)]
+pub struct ActiveEnvironment {
+    pub environment: Handle<LevelEnvironment>,
+}
+
+impl ActiveEnvironment {
+    pub fn new(environment: Handle<LevelEnvironment>) -> Self {
+        Self { environment }
+    }
+}
+
+/// The scene's directional light, set from the level environment's sun
+#[derive(Component)]
+pub struct Sun;
+
+/// what was there before any level changed it, restored on teardown
+struct EnvironmentDefaults {
+    layout: CubemapLayout,
+    sources: Vec<Handle<Image>>,
+    exposure: f32,
+    ambient_color: Color,
+    ambient_brightness: f32,
+    sun: Option<(DirectionalLight, Transform)>,
+}
+
+#[derive(Resource, Default)]
+struct EnvironmentState {
+    applied: Option<Handle<LevelEnvironment>>,
+    defaults: Option<EnvironmentDefaults>,
+    fog: Option<FogSettings>,
+    /// cubes already built for a level's sky, a restart would otherwise convert it again
+    skyboxes: HashMap<SkyboxDescriptor, Handle<Image>>,
+    /// the sky being built and the cube shown before it, a load that fails keeps the old cube
+    pending_skybox: Option<(SkyboxDescriptor, Handle<Image>)>,
+}
+
+fn apply_environment(
+    active: Option<Res<ActiveEnvironment>>,
+    environments: Res<Assets<LevelEnvironment>>,
+    mut state: ResMut<EnvironmentState>,
+    mut cubemap: ResMut<Cubemap>,
+    mut ambient: ResMut<AmbientLight>,
+    asset_server: Res<AssetServer>,
+    mut sun_query: Query<(&mut DirectionalLight, &mut Transform), With<Sun>>,
+) {
+    let Some(active) = active else {
+        return;
+    };
+    if state.applied.as_ref() == Some(&active.environment) {
+        return;
+    }
+    let Some(environment) = environments.get(&active.environment) else {
+        return;
+    };
+
+    if state.defaults.is_none() {
+        // the sources are dropped once the cube is built, keep the cube itself instead
+        let (layout, sources) = if cubemap.is_loaded {
+            (CubemapLayout::Cube, vec![cubemap.image_handle.clone()])
+        } else {
+            (cubemap.layout, cubemap.sources.clone())
+        };
+        state.defaults = Some(EnvironmentDefaults {
+            layout,
+            sources,
+            exposure: cubemap.exposure,
+            ambient_color: ambient.color,
+            ambient_brightness: ambient.brightness,
+            sun: sun_query
+                .iter()
+                .next()
+                .map(|(light, transform)| (light.clone(), *transform)),
+        });
+    }
+    state.applied = Some(active.environment.clone());
+
+    if let Some(cube) = state.skyboxes.get(&environment.skybox) {
+        let cube = cube.clone();
+        set_skybox_images(&mut cubemap, CubemapLayout::Cube, vec![cube]);
+        state.pending_skybox = None;
+    } else {
+        match &environment.skybox {
+            SkyboxDescriptor::Image(path) => set_skybox_path(&mut cubemap, &asset_server, path),
+            SkyboxDescriptor::Faces(paths) => set_skybox_faces(&mut cubemap, &asset_server, paths),
+        }
+        state.pending_skybox = Some((environment.skybox.clone(), cubemap.image_handle.clone()));
+    }
+    cubemap.exposure = environment.exposure;
+
+    let (r, g, b) = environment.ambient.color;
+    ambient.color = Color::rgb(r, g, b);
+    ambient.brightness = environment.ambient.brightness;
+
+    // without a sun of its own the level keeps the default light, a scene extra can swap the
+    // environment without a teardown in between so it is restored here too
+    match environment.sun {
+        Some(sun) => {
+            let (r, g, b) = sun.color;
+            let (x, y, z) = sun.direction;
+            let direction = Vec3::new(x, y, z).try_normalize().unwrap_or(Vec3::NEG_Y);
+            let up = if direction.y.abs() > 0.99 {
+                Vec3::Z
+            } else {
+                Vec3::Y
+            };
+            for (mut light, mut transform) in sun_query.iter_mut() {
+                light.color = Color::rgb(r, g, b);
+                light.illuminance = sun.illuminance;
+                light.shadows_enabled = sun.shadows;
+                *transform = Transform::IDENTITY.looking_to(direction, up);
+            }
+        }
+        None => restore_sun(&state, &mut sun_query),
+    }
+
+    state.fog = environment.fog.map(|fog| {
+        let (r, g, b, a) = fog.color;
+        FogSettings {
+            color: Color::rgba(r, g, b, a),
+            falloff: FogFalloff::Linear {
+                start: fog.start,
+                end: fog.end,
+            },
+            ..default()
+        }
+    });
+}
+
+/// the player camera is spawned again with the player, so new cameras get the fog too
+fn apply_fog(
+    mut commands: Commands,
+    state: Res<EnvironmentState>,
+    camera_query: Query<Entity, With<Camera>>,
+    new_camera_query: Query<Entity, Added<Camera>>,
+) {
+    let cameras: Vec<Entity> = if state.is_changed() {
+        camera_query.iter().collect()
+    } else {
+        new_camera_query.iter().collect()
+    };
+    for camera in cameras {
+        match &state.fog {
+            Some(fog) => commands.entity(camera).insert(fog.clone()),
+            None => commands.entity(camera).remove::<FogSettings>(),
+        };
+    }
+}
+
+/// keep the cube of a level's sky once it is built
+fn cache_skybox(mut state: ResMut<EnvironmentState>, cubemap: Res<Cubemap>) {
+    if !cubemap.is_loaded {
+        return;
+    }
+    let Some((skybox, previous)) = state.pending_skybox.take() else {
+        return;
+    };
+    if cubemap.image_handle != previous {
+        state.skyboxes.insert(skybox, cubemap.image_handle.clone());
+    }
+}
+
+fn restore_sun(
+    state: &EnvironmentState,
+    sun_query: &mut Query<(&mut DirectionalLight, &mut Transform), With<Sun>>,
+) {
+    let Some((default_light, default_transform)) = state
+        .defaults
+        .as_ref()
+        .and_then(|defaults| defaults.sun.as_ref())
+    else {
+        return;
+    };
+    for (mut light, mut transform) in sun_query.iter_mut() {
+        *light = default_light.clone();
+        *transform = *default_transform;
+    }
+}
+
+fn on_teardown(
+    mut state: ResMut<EnvironmentState>,
+    mut cubemap: ResMut<Cubemap>,
+    mut ambient: ResMut<AmbientLight>,
+    mut ev_teardown: EventReader<TeardownLevelEvent>,
+    mut sun_query: Query<(&mut DirectionalLight, &mut Transform), With<Sun>>,
+) {
+    for _ in ev_teardown.iter() {
+        state.applied = None;
+        state.fog = None;
+        // the default sky replaces one still being built
+        state.pending_skybox = None;
+        restore_sun(&state, &mut sun_query);
+        let Some(defaults) = &state.defaults else {
+            continue;
+        };
+        set_skybox_images(&mut cubemap, defaults.layout, defaults.sources.clone());
+        cubemap.exposure = defaults.exposure;
+        ambient.color = defaults.ambient_color;
+        ambient.brightness = defaults.ambient_brightness;
+    }
+}
+
+#[derive(Default)]
+pub struct LevelEnvironmentLoader;
+
+impl AssetLoader for LevelEnvironmentLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let environment = ron::de::from_bytes::<LevelEnvironment>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(environment));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["environment.ron"]
+    }
+}
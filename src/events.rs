@@ -1,26 +1,98 @@
 use bevy::prelude::*;
 
-pub struct ExplosionEvent(pub Transform);
+use crate::bullet::ProjectileKind;
+use crate::faction::Faction;
+
+/// What an effect event shows, each kind plays a different effect
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EffectKind {
+    /// a projectile hitting a hull or the level
+    Impact,
+    /// a hit fully absorbed by a shield
+    ShieldHit,
+    /// something destroyed, also throws debris
+    Destruction,
+}
+
+pub struct EffectEvent {
+    pub kind: EffectKind,
+    pub position: Vec3,
+    /// surface normal at the hit, pointing back towards whatever caused it
+    pub normal: Vec3,
+    /// rough radius of what was hit or destroyed
+    pub size: f32,
+}
+
+/// play an effect from the effect library by name
+pub struct SpawnEffectEvent {
+    pub name: String,
+    pub transform: Transform,
+}
 
 pub struct SpawnBulletEvent {
+    pub kind: ProjectileKind,
+    /// beams are attached to the shooter
+    pub shooter: Entity,
     pub transform: Transform,
     pub direction: Vec3,
     pub speed: f32,
+    pub faction: Faction,
+    pub damage: f32,
+}
+
+/// damage to an entity, routed through its invulnerability, shield and health
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: f32,
+    /// faction of whatever dealt the damage
+    pub source: Faction,
+    /// where the damage landed
+    pub point: Vec3,
+    /// surface normal at the point, pointing back towards the source
+    pub normal: Vec3,
+    /// a slice of damage dealt over time, ex: a beam, it ignores invulnerability windows
+    pub continuous: bool,
 }
 
+pub struct EnemyDestroyedEvent {
+    /// the squadron it flew in, the enemy is already despawned when this is read
+    pub squadron: Option<Entity>,
+    /// points awarded for it
+    pub score: u32,
+}
+
+pub struct SquadronClearedEvent {
+    pub bonus: u32,
+}
+
+/// the player's health reached zero and the ship is gone, the level restarts shortly after
+pub struct PlayerDestroyedEvent;
+
 pub struct SpawnPlayerEvent {
     pub transform: Transform,
 }
 
+/// spawn a boss from the enemy registry by name
+pub struct SpawnBossEvent {
+    pub boss: String,
+    pub transform: Transform,
+}
+
 pub struct TeardownLevelEvent;
 
 pub struct EventPlugin;
 
 impl Plugin for EventPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<ExplosionEvent>()
+        app.add_event::<EffectEvent>()
+            .add_event::<SpawnEffectEvent>()
             .add_event::<SpawnBulletEvent>()
+            .add_event::<DamageEvent>()
+            .add_event::<EnemyDestroyedEvent>()
+            .add_event::<SquadronClearedEvent>()
+            .add_event::<PlayerDestroyedEvent>()
             .add_event::<SpawnPlayerEvent>()
+            .add_event::<SpawnBossEvent>()
             .add_event::<TeardownLevelEvent>();
     }
 }
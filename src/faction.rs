@@ -0,0 +1,57 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::{CollisionGroups, Group};
+
+pub const PLAYER_GROUP: Group = Group::GROUP_1;
+pub const PLAYER_PROJECTILE_GROUP: Group = Group::GROUP_2;
+pub const ENEMY_GROUP: Group = Group::GROUP_3;
+pub const ENEMY_PROJECTILE_GROUP: Group = Group::GROUP_4;
+pub const TERRAIN_GROUP: Group = Group::GROUP_5;
+pub const PICKUP_GROUP: Group = Group::GROUP_6;
+pub const DEBRIS_GROUP: Group = Group::GROUP_7;
+
+/// Which side an entity fights for, projectiles only damage entities of another faction
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Faction {
+    Player,
+    Enemy,
+}
+
+impl Faction {
+    /// collision groups for ships and anything else that fights for this faction
+    pub fn body_groups(&self) -> CollisionGroups {
+        match self {
+            Faction::Player => CollisionGroups::new(
+                PLAYER_GROUP,
+                ENEMY_GROUP | ENEMY_PROJECTILE_GROUP | TERRAIN_GROUP | PICKUP_GROUP,
+            ),
+            // enemies pass through each other and the level
+            Faction::Enemy => {
+                CollisionGroups::new(ENEMY_GROUP, PLAYER_GROUP | PLAYER_PROJECTILE_GROUP)
+            }
+        }
+    }
+
+    /// collision groups for projectiles fired by this faction, they never touch each other
+    pub fn projectile_groups(&self) -> CollisionGroups {
+        match self {
+            Faction::Player => {
+                CollisionGroups::new(PLAYER_PROJECTILE_GROUP, ENEMY_GROUP | TERRAIN_GROUP)
+            }
+            Faction::Enemy => {
+                CollisionGroups::new(ENEMY_PROJECTILE_GROUP, PLAYER_GROUP | TERRAIN_GROUP)
+            }
+        }
+    }
+}
+
+pub fn terrain_groups() -> CollisionGroups {
+    CollisionGroups::new(
+        TERRAIN_GROUP,
+        PLAYER_GROUP | PLAYER_PROJECTILE_GROUP | ENEMY_PROJECTILE_GROUP | DEBRIS_GROUP,
+    )
+}
+
+/// wreckage only bounces off the level
+pub fn debris_groups() -> CollisionGroups {
+    CollisionGroups::new(DEBRIS_GROUP, TERRAIN_GROUP)
+}
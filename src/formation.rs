@@ -0,0 +1,216 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::f32::consts::TAU;
+
+use crate::events::{EnemyDestroyedEvent, SquadronClearedEvent, TeardownLevelEvent};
+
+pub struct FormationPlugin;
+
+impl Plugin for FormationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(track_squadrons).add_system(on_teardown);
+    }
+}
+
+/// Layout of a squadron, offsets are in the local frame of the squadron (x right, y up, +z behind)
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum Formation {
+    V,
+    Line,
+    Ring,
+    StaggeredColumn,
+}
+
+impl Formation {
+    pub fn offsets(&self, count: usize, spacing: f32) -> Vec<Vec3> {
+        (0..count)
+            .map(|i| match self {
+                // leader in front, pairs trailing behind on either side
+                Formation::V => {
+                    let rank = i.div_ceil(2) as f32;
+                    let side = if i % 2 == 0 { 1.0 } else { -1.0 };
+                    Vec3::new(side * rank * spacing, 0.0, rank * spacing)
+                }
+                // side by side, centered
+                Formation::Line => {
+                    Vec3::new((i as f32 - (count - 1) as f32 / 2.0) * spacing, 0.0, 0.0)
+                }
+                // evenly around a circle facing forward
+                Formation::Ring => {
+                    let radius = (spacing * count as f32 / TAU).max(spacing);
+                    let angle = TAU * i as f32 / count as f32;
+                    Vec3::new(angle.cos() * radius, angle.sin() * radius, 0.0)
+                }
+                // one behind the other, alternating left and right
+                Formation::StaggeredColumn => {
+                    let side = if i % 2 == 0 { -0.5 } else { 0.5 };
+                    Vec3::new(side * spacing, 0.0, i as f32 * spacing)
+                }
+            })
+            .collect()
+    }
+}
+
+/// A group of enemies that awards a bonus if every member is destroyed
+#[derive(Component)]
+pub struct Squadron {
+    pub members: Vec<Entity>,
+    pub size: usize,
+    pub destroyed: usize,
+    pub bonus: u32,
+}
+
+impl Squadron {
+    pub fn new(members: Vec<Entity>, bonus: u32) -> Self {
+        Self {
+            size: members.len(),
+            members,
+            destroyed: 0,
+            bonus,
+        }
+    }
+}
+
+/// points from an enemy back to its squadron
+#[derive(Component)]
+pub struct SquadronMember(pub Entity);
+
+/// count destroyed members, once every member is gone decide if the squadron was cleared
+fn track_squadrons(
+    mut commands: Commands,
+    mut squadron_query: Query<(Entity, &mut Squadron)>,
+    member_query: Query<&SquadronMember>,
+    mut ev_destroyed: EventReader<EnemyDestroyedEvent>,
+    mut ev_cleared: EventWriter<SquadronClearedEvent>,
+) {
+    // destroyed events are read before checking which members still exist,
+    // so a member is always counted before it is seen as gone
+    for ev in ev_destroyed.iter() {
+        let Some(squadron) = ev.squadron else {
+            continue;
+        };
+        if let Ok((_, mut squadron)) = squadron_query.get_mut(squadron) {
+            squadron.destroyed += 1;
+            if squadron.destroyed == squadron.size {
+                ev_cleared.send(SquadronClearedEvent {
+                    bonus: squadron.bonus,
+                });
+            }
+        }
+    }
+
+    for (entity, mut squadron) in squadron_query.iter_mut() {
+        squadron
+            .members
+            .retain(|member| member_query.contains(*member));
+        if squadron.members.is_empty() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn on_teardown(
+    mut commands: Commands,
+    query: Query<Entity, With<Squadron>>,
+    mut ev_teardown: EventReader<TeardownLevelEvent>,
+) {
+    for _ in ev_teardown.iter() {
+        for entity in query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_offsets(formation: Formation, spacing: f32, expected: &[Vec3]) {
+        let offsets = formation.offsets(expected.len(), spacing);
+        assert_eq!(offsets.len(), expected.len());
+        for (i, (offset, expected)) in offsets.iter().zip(expected).enumerate() {
+            assert!(
+                offset.abs_diff_eq(*expected, 1e-5),
+                "{:?} slot {}: {:?}, expected {:?}",
+                formation,
+                i,
+                offset,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn v_trails_pairs_behind_the_leader() {
+        let odd = [
+            Vec3::ZERO,
+            Vec3::new(-2.0, 0.0, 2.0),
+            Vec3::new(2.0, 0.0, 2.0),
+        ];
+        assert_offsets(Formation::V, 2.0, &odd);
+
+        let even = [
+            Vec3::ZERO,
+            Vec3::new(-2.0, 0.0, 2.0),
+            Vec3::new(2.0, 0.0, 2.0),
+            Vec3::new(-4.0, 0.0, 4.0),
+        ];
+        assert_offsets(Formation::V, 2.0, &even);
+    }
+
+    #[test]
+    fn line_is_centered() {
+        let odd = [
+            Vec3::new(-2.0, 0.0, 0.0),
+            Vec3::ZERO,
+            Vec3::new(2.0, 0.0, 0.0),
+        ];
+        assert_offsets(Formation::Line, 2.0, &odd);
+
+        let even = [
+            Vec3::new(-3.0, 0.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(3.0, 0.0, 0.0),
+        ];
+        assert_offsets(Formation::Line, 2.0, &even);
+    }
+
+    #[test]
+    fn staggered_column_alternates_sides() {
+        let odd = [
+            Vec3::new(-1.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 2.0),
+            Vec3::new(-1.0, 0.0, 4.0),
+        ];
+        assert_offsets(Formation::StaggeredColumn, 2.0, &odd);
+
+        let even = [
+            Vec3::new(-1.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 2.0),
+            Vec3::new(-1.0, 0.0, 4.0),
+            Vec3::new(1.0, 0.0, 6.0),
+        ];
+        assert_offsets(Formation::StaggeredColumn, 2.0, &even);
+    }
+
+    #[test]
+    fn ring_is_evenly_spaced_around_the_center() {
+        // small rings are never tighter than the spacing
+        let even = [
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+            Vec3::new(-2.0, 0.0, 0.0),
+            Vec3::new(0.0, -2.0, 0.0),
+        ];
+        assert_offsets(Formation::Ring, 2.0, &even);
+
+        let odd = Formation::Ring.offsets(9, 2.0);
+        let radius = 2.0 * 9.0 / TAU;
+        for offset in odd.iter() {
+            assert!((offset.length() - radius).abs() < 1e-4);
+        }
+        let center: Vec3 = odd.iter().sum::<Vec3>() / odd.len() as f32;
+        assert!(center.abs_diff_eq(Vec3::ZERO, 1e-4));
+    }
+}
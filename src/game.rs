@@ -2,14 +2,32 @@ use bevy::prelude::*;
 // use bevy_editor_pls::prelude::*;
 use bevy_rapier3d::prelude::*;
 
+use crate::behaviour::BehaviourPlugin;
+use crate::boss::BossPlugin;
 use crate::bullet::BulletPlugin;
 use crate::constants::make_cam_entity;
+use crate::debris::DebrisPlugin;
+use crate::difficulty::DifficultyPlugin;
+use crate::dust::DustPlugin;
 use crate::enemy::EnemyPlugin;
+use crate::enemy_weapon::EnemyWeaponPlugin;
+use crate::environment::Sun;
 use crate::events::EventPlugin;
+use crate::formation::FormationPlugin;
+use crate::health::HealthPlugin;
 use crate::level::LevelPlugin;
+use crate::lifetime::LifetimePlugin;
 use crate::particles::ParticlePlugin;
 use crate::player::PlayerPlugin;
+use crate::pool::PoolPlugin;
+use crate::rail::RailPlugin;
+use crate::rng::RngPlugin;
+use crate::score::ScorePlugin;
+use crate::thruster::ThrusterPlugin;
+use crate::turret::TurretPlugin;
 use crate::velocity::VelocityPlugin;
+use crate::waves::WavePlugin;
+use crate::weapon::WeaponPlugin;
 
 pub struct GamePlugin;
 
@@ -17,12 +35,29 @@ impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
         app.add_startup_system(setup)
             .add_plugin(EventPlugin)
+            .add_plugin(RngPlugin)
+            .add_plugin(DifficultyPlugin)
             .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
             .add_plugin(VelocityPlugin)
+            .add_plugin(LifetimePlugin)
+            .add_plugin(PoolPlugin)
             .add_plugin(PlayerPlugin)
+            .add_plugin(RailPlugin)
             .add_plugin(EnemyPlugin)
+            .add_plugin(WavePlugin)
+            .add_plugin(BehaviourPlugin)
+            .add_plugin(EnemyWeaponPlugin)
+            .add_plugin(FormationPlugin)
+            .add_plugin(BossPlugin)
+            .add_plugin(TurretPlugin)
             .add_plugin(BulletPlugin)
+            .add_plugin(HealthPlugin)
+            .add_plugin(ScorePlugin)
+            .add_plugin(WeaponPlugin)
             .add_plugin(ParticlePlugin)
+            .add_plugin(ThrusterPlugin)
+            .add_plugin(DebrisPlugin)
+            .add_plugin(DustPlugin)
             //.add_plugin(RapierDebugRenderPlugin::default()) // disable hdr to use
             .add_plugin(LevelPlugin)
             // .add_plugin(EditorPlugin)
@@ -31,13 +66,16 @@ impl Plugin for GamePlugin {
 }
 
 fn setup(mut commands: Commands) {
-    const HALF_SIZE: f32 = 1.0;
-    commands.spawn(DirectionalLightBundle {
-        directional_light: DirectionalLight {
-            shadows_enabled: true,
+    // levels point and color it from their environment
+    commands.spawn((
+        DirectionalLightBundle {
+            directional_light: DirectionalLight {
+                shadows_enabled: true,
+                ..default()
+            },
             ..default()
         },
-        ..default()
-    });
+        Sun,
+    ));
     commands.spawn(make_cam_entity(Transform::IDENTITY));
 }
@@ -0,0 +1,452 @@
+use bevy::ecs::system::Command;
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use bevy_rapier3d::prelude::Collider;
+
+use crate::enemy::ScoreValue;
+use crate::events::{
+    DamageEvent, EffectEvent, EffectKind, EnemyDestroyedEvent, PlayerDestroyedEvent,
+};
+use crate::faction::Faction;
+use crate::formation::SquadronMember;
+use crate::pool::PoolCommandsExt;
+
+const HIT_FLASH_TIME: f32 = 0.1;
+const HIT_FLASH_COLOR: Color = Color::rgb(4.0, 1.0, 1.0);
+/// effect size for entities without a collider
+const DEFAULT_EFFECT_SIZE: f32 = 1.0;
+
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HitFlashMaterials>()
+            .add_system(apply_damage)
+            .add_system(recharge_shields)
+            .add_system(tick_invulnerability)
+            .add_system(update_hit_flash);
+    }
+}
+
+#[derive(Component)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.0
+    }
+
+    /// returns true only for the hit that brings health to zero
+    pub fn apply_damage(&mut self, amount: f32) -> bool {
+        let was_alive = !self.is_dead();
+        self.current = (self.current - amount).max(0.0);
+        was_alive && self.is_dead()
+    }
+}
+
+/// damage dealt by a projectile when it hits something
+#[derive(Component, Clone, Copy)]
+pub struct Damage(pub f32);
+
+/// Absorbs damage before health, recharges once nothing has hit it for a while
+#[derive(Component)]
+pub struct Shield {
+    pub current: f32,
+    pub max: f32,
+    /// shield restored every second while recharging
+    pub recharge_rate: f32,
+    /// seconds without damage before recharging starts
+    pub recharge_delay: f32,
+    since_hit: f32,
+}
+
+impl Shield {
+    pub fn new(max: f32, recharge_rate: f32, recharge_delay: f32) -> Self {
+        Self {
+            current: max,
+            max,
+            recharge_rate,
+            recharge_delay,
+            since_hit: 0.0,
+        }
+    }
+
+    /// returns the damage left over once the shield is depleted
+    pub fn absorb(&mut self, amount: f32) -> f32 {
+        self.since_hit = 0.0;
+        let absorbed = amount.min(self.current);
+        self.current -= absorbed;
+        amount - absorbed
+    }
+
+    /// recharge over `dt` seconds once the delay since the last hit has passed
+    fn recharge(&mut self, dt: f32) {
+        self.since_hit += dt;
+        if self.since_hit >= self.recharge_delay {
+            self.current = (self.current + self.recharge_rate * dt).min(self.max);
+        }
+    }
+}
+
+/// seconds an entity ignores damage after being hurt
+#[derive(Component)]
+pub struct InvulnerabilityWindow(pub f32);
+
+/// ignores all damage until the timer finishes
+#[derive(Component)]
+pub struct Invulnerable {
+    timer: Timer,
+}
+
+impl Invulnerable {
+    pub fn new(seconds: f32) -> Self {
+        Self {
+            timer: Timer::from_seconds(seconds, TimerMode::Once),
+        }
+    }
+}
+
+/// everything that decides how a hit lands and what happens when it kills
+type DamageTarget<'a> = (
+    &'a mut Health,
+    &'a GlobalTransform,
+    Option<&'a Faction>,
+    Option<&'a Collider>,
+    Option<&'a mut Shield>,
+    Option<&'a InvulnerabilityWindow>,
+    Option<&'a Invulnerable>,
+    Option<&'a SquadronMember>,
+    Option<&'a ScoreValue>,
+);
+
+/// route damage through invulnerability, shields and health, then handle deaths
+fn apply_damage(
+    mut commands: Commands,
+    mut query: Query<DamageTarget<'static>>,
+    mut ev_damage: EventReader<DamageEvent>,
+    mut ev_destroyed: EventWriter<EnemyDestroyedEvent>,
+    mut ev_effect: EventWriter<EffectEvent>,
+    mut ev_player_destroyed: EventWriter<PlayerDestroyedEvent>,
+) {
+    // invulnerable is only inserted once commands apply, later hits this frame check here
+    let mut protected = HashSet::new();
+    let mut killed = HashSet::new();
+    for ev in ev_damage.iter() {
+        let Ok((
+            mut health,
+            global_transform,
+            faction,
+            collider,
+            shield,
+            window,
+            invulnerable,
+            member,
+            score,
+        )) = query.get_mut(ev.target)
+        else {
+            continue;
+        };
+        let ignoring = invulnerable.is_some() || protected.contains(&ev.target);
+        if health.is_dead() || killed.contains(&ev.target) || (ignoring && !ev.continuous) {
+            continue;
+        }
+
+        let amount = match shield {
+            Some(mut shield) => shield.absorb(ev.amount),
+            None => ev.amount,
+        };
+        // a hit on the shield still starts the window so one burst can't strip everything,
+        // damage over time would keep restarting it and block everything else
+        if let Some(window) = window.filter(|_| !ev.continuous) {
+            commands.add(InsertIfAlive(ev.target, Invulnerable::new(window.0)));
+            protected.insert(ev.target);
+        }
+
+        let size = collider.map_or(DEFAULT_EFFECT_SIZE, |collider| {
+            collider.raw.compute_local_bounding_sphere().radius()
+        });
+        let mut effect = EffectEvent {
+            kind: EffectKind::ShieldHit,
+            position: ev.point,
+            normal: ev.normal,
+            size,
+        };
+        if amount <= 0.0 {
+            ev_effect.send(effect);
+            continue;
+        }
+
+        // flash on hit, only explode once health reaches zero
+        if !health.apply_damage(amount) {
+            commands.add(InsertIfAlive(ev.target, HitFlash::default()));
+            effect.kind = EffectKind::Impact;
+            ev_effect.send(effect);
+            continue;
+        }
+        killed.insert(ev.target);
+        // debris is thrown out from the middle of the wreck
+        effect.kind = EffectKind::Destruction;
+        effect.position = global_transform.translation();
+        ev_effect.send(effect);
+        match faction {
+            Some(Faction::Player) => ev_player_destroyed.send(PlayerDestroyedEvent),
+            _ => ev_destroyed.send(EnemyDestroyedEvent {
+                squadron: member.map(|member| member.0),
+                score: score.map_or(0, |score| score.0),
+            }),
+        }
+        // a destroyed player ship can't keep flying, shooting or taking hits until the restart
+        commands.despawn_pooled(ev.target);
+    }
+}
+
+/// another system may despawn the target before commands apply, inserting on it would panic
+struct InsertIfAlive<B: Bundle>(Entity, B);
+
+impl<B: Bundle> Command for InsertIfAlive<B> {
+    fn write(self, world: &mut World) {
+        if let Some(mut entity) = world.get_entity_mut(self.0) {
+            entity.insert(self.1);
+        }
+    }
+}
+
+fn recharge_shields(mut query: Query<&mut Shield>, time: Res<Time>) {
+    for mut shield in query.iter_mut() {
+        shield.recharge(time.delta_seconds());
+    }
+}
+
+fn tick_invulnerability(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Invulnerable)>,
+    time: Res<Time>,
+) {
+    for (entity, mut invulnerable) in query.iter_mut() {
+        if invulnerable.timer.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Invulnerable>();
+        }
+    }
+}
+
+/// tints every material of an entity's scene for a short time, inserting it again restarts the flash
+#[derive(Component)]
+pub struct HitFlash {
+    timer: Timer,
+}
+
+impl Default for HitFlash {
+    fn default() -> Self {
+        Self {
+            timer: Timer::from_seconds(HIT_FLASH_TIME, TimerMode::Once),
+        }
+    }
+}
+
+/// the material a mesh had before it was swapped for the flash material
+#[derive(Component)]
+struct FlashedMaterial(Handle<StandardMaterial>);
+
+/// tinted copies of materials, so every flash of the same model reuses the same material
+#[derive(Resource, Default)]
+struct HitFlashMaterials(HashMap<Handle<StandardMaterial>, Handle<StandardMaterial>>);
+
+fn update_hit_flash(
+    mut commands: Commands,
+    mut flash_query: Query<(Entity, &mut HitFlash)>,
+    children_query: Query<&Children>,
+    mut mesh_query: Query<(&mut Handle<StandardMaterial>, Option<&FlashedMaterial>)>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut flash_materials: ResMut<HitFlashMaterials>,
+    time: Res<Time>,
+) {
+    for (entity, mut flash) in flash_query.iter_mut() {
+        flash.timer.tick(time.delta());
+        let finished = flash.timer.finished();
+
+        // the material can be on the entity itself or anywhere in its scene
+        for descendant in std::iter::once(entity).chain(children_query.iter_descendants(entity)) {
+            let Ok((mut material, flashed)) = mesh_query.get_mut(descendant) else {
+                continue;
+            };
+            match (finished, flashed) {
+                // swap in the tinted material
+                (false, None) => {
+                    let original = material.clone();
+                    let tinted = match flash_materials.0.get(&original) {
+                        Some(tinted) => tinted.clone(),
+                        None => {
+                            let mut tinted = match materials.get(&original) {
+                                Some(source) => source.clone(),
+                                None => continue,
+                            };
+                            tinted.emissive = HIT_FLASH_COLOR;
+                            let tinted = materials.add(tinted);
+                            flash_materials.0.insert(original.clone(), tinted.clone());
+                            tinted
+                        }
+                    };
+                    *material = tinted;
+                    commands
+                        .entity(descendant)
+                        .insert(FlashedMaterial(original));
+                }
+                // restore the original material
+                (true, Some(original)) => {
+                    *material = original.0.clone();
+                    commands.entity(descendant).remove::<FlashedMaterial>();
+                }
+                _ => {}
+            }
+        }
+
+        if finished {
+            commands.entity(entity).remove::<HitFlash>();
+        }
+    }
+}
+
+/// put the original materials back on a scene, for scene instances that outlive their flashing entity
+pub fn clear_hit_flash(world: &mut World, entity: Entity) {
+    let mut stack = vec![entity];
+    while let Some(entity) = stack.pop() {
+        if let Some(children) = world.get::<Children>(entity) {
+            stack.extend(children.iter().copied());
+        }
+        let Some(original) = world.get::<FlashedMaterial>(entity).map(|f| f.0.clone()) else {
+            continue;
+        };
+        world
+            .entity_mut(entity)
+            .insert(original)
+            .remove::<FlashedMaterial>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn hit(target: Entity, amount: f32, continuous: bool) -> DamageEvent {
+        DamageEvent {
+            target,
+            amount,
+            source: Faction::Enemy,
+            point: Vec3::ZERO,
+            normal: Vec3::Z,
+            continuous,
+        }
+    }
+
+    /// run apply_damage once over the given hits
+    fn damage(world: &mut World, hits: Vec<DamageEvent>) {
+        world.init_resource::<Events<DamageEvent>>();
+        world.init_resource::<Events<EnemyDestroyedEvent>>();
+        world.init_resource::<Events<EffectEvent>>();
+        world.init_resource::<Events<PlayerDestroyedEvent>>();
+        world.resource_mut::<Events<DamageEvent>>().extend(hits);
+
+        let mut schedule = Schedule::new();
+        schedule.add_system(apply_damage);
+        schedule.run(world);
+    }
+
+    #[test]
+    fn only_the_killing_hit_reports_death() {
+        let mut health = Health::new(3.0);
+        assert!(!health.apply_damage(2.0));
+        assert!(health.apply_damage(2.0));
+        assert_eq!(health.current, 0.0);
+        // already dead, further hits are not another kill
+        assert!(!health.apply_damage(1.0));
+    }
+
+    #[test]
+    fn shield_absorbs_damage_before_health() {
+        let mut shield = Shield::new(2.0, 1.0, 1.0);
+        assert_eq!(shield.absorb(1.5), 0.0);
+        assert_eq!(shield.current, 0.5);
+        // only what the shield could not hold goes through
+        assert_eq!(shield.absorb(2.0), 1.5);
+        assert_eq!(shield.current, 0.0);
+    }
+
+    #[test]
+    fn shield_recharges_after_the_delay() {
+        let mut shield = Shield::new(2.0, 1.0, 1.0);
+        shield.absorb(2.0);
+        shield.recharge(0.5);
+        assert_eq!(shield.current, 0.0);
+        shield.recharge(0.5);
+        assert_eq!(shield.current, 0.5);
+        shield.recharge(5.0);
+        assert_eq!(shield.current, shield.max);
+
+        // a new hit restarts the delay
+        shield.absorb(1.0);
+        shield.recharge(0.5);
+        assert_eq!(shield.current, 1.0);
+    }
+
+    #[test]
+    fn shield_and_health_split_a_hit() {
+        let mut world = World::new();
+        let target = world
+            .spawn((
+                Health::new(5.0),
+                Shield::new(2.0, 1.0, 1.0),
+                GlobalTransform::default(),
+            ))
+            .id();
+        damage(&mut world, vec![hit(target, 3.0, false)]);
+
+        let entity = world.entity(target);
+        assert_eq!(entity.get::<Shield>().unwrap().current, 0.0);
+        assert_eq!(entity.get::<Health>().unwrap().current, 4.0);
+    }
+
+    #[test]
+    fn window_ignores_later_hits_but_not_continuous_damage() {
+        let mut world = World::new();
+        let target = world
+            .spawn((
+                Health::new(10.0),
+                InvulnerabilityWindow(0.5),
+                GlobalTransform::default(),
+            ))
+            .id();
+        damage(
+            &mut world,
+            vec![
+                hit(target, 1.0, false),
+                hit(target, 1.0, false),
+                hit(target, 0.5, true),
+            ],
+        );
+
+        let entity = world.entity(target);
+        assert_eq!(entity.get::<Health>().unwrap().current, 8.5);
+        assert!(entity.contains::<Invulnerable>());
+    }
+
+    #[test]
+    fn invulnerability_lasts_the_window() {
+        let mut invulnerable = Invulnerable::new(0.5);
+        assert!(!invulnerable
+            .timer
+            .tick(Duration::from_secs_f32(0.25))
+            .finished());
+        assert!(invulnerable
+            .timer
+            .tick(Duration::from_secs_f32(0.25))
+            .finished());
+    }
+}
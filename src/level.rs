@@ -1,25 +1,34 @@
 use bevy::{gltf::GltfExtras, prelude::*};
 
 use bevy_asset_loader::prelude::*;
+use bevy_rapier3d::prelude::*;
 
 use crate::{
-    events::{SpawnPlayerEvent, TeardownLevelEvent},
-    skybox::{set_skybox_texture, Cubemap, SkyboxPlugin, SkyboxState},
+    boss::BossArena,
+    enemy_registry::{EnemyAssets, EnemyRegistryPlugin},
+    environment::{ActiveEnvironment, EnvironmentPlugin, LevelEnvironment},
+    events::{PlayerDestroyedEvent, SpawnPlayerEvent, TeardownLevelEvent},
+    faction::terrain_groups,
+    skybox::SkyboxPlugin,
+    turret::TurretMarker,
+    waves::{WaveDirector, WaveScript},
 };
 
+/// seconds between the player being destroyed and the level restarting
+const RESTART_DELAY: f32 = 2.0;
+
 pub struct LevelPlugin;
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, States)]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash, States)]
 pub enum LevelState {
+    #[default]
     Loading,
     Loaded,
 }
 
-impl Default for LevelState {
-    fn default() -> Self {
-        LevelState::Loading
-    }
-}
+/// Where levels are torn down and loaded, for systems that have to run around a level change
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ChangeLevelSet;
 
 #[derive(AssetCollection, Resource)]
 struct LevelAssets {
@@ -27,16 +36,41 @@ struct LevelAssets {
     lv_test: Handle<Scene>,
     #[asset(path = "models/LV1/lv_1.gltf#Scene0")]
     lv_1: Handle<Scene>,
+    #[asset(path = "waves/lv_test.waves.ron")]
+    lv_test_waves: Handle<WaveScript>,
+    #[asset(path = "waves/lv_1.waves.ron")]
+    lv_1_waves: Handle<WaveScript>,
+    #[asset(path = "environments/lv_test.environment.ron")]
+    lv_test_environment: Handle<LevelEnvironment>,
+    #[asset(path = "environments/lv_1.environment.ron")]
+    lv_1_environment: Handle<LevelEnvironment>,
 }
 
 #[derive(Component)]
 struct Level;
 
+/// The level being played, restarted after the player is destroyed
+#[derive(Resource, Default)]
+struct CurrentLevel {
+    index: usize,
+    restart: Option<Timer>,
+    reload: bool,
+}
+
 #[derive(Component)]
 struct PlayerPathRaw {
     parent_transform: Transform,
+    name: Option<String>,
 }
 
+/// level geometry that blocks line of sight, the collider is built once the mesh has loaded
+#[derive(Component)]
+struct LevelColliderRaw;
+
+/// marks a path that is not the player rail, enemies can look it up by name
+#[derive(Component)]
+pub struct NamedPath(pub String);
+
 #[derive(Component)]
 pub struct PlayerPath {
     pub points: Vec<PathTransformDescriptor>,
@@ -69,7 +103,7 @@ impl PlayerPath {
             }
         }
 
-        return Transform::IDENTITY;
+        Transform::IDENTITY
     }
 }
 
@@ -81,17 +115,31 @@ pub struct PathTransformDescriptor {
 
 impl Plugin for LevelPlugin {
     fn build(&self, app: &mut App) {
-        app.add_state::<LevelState>()
+        app.add_plugin(EnemyRegistryPlugin)
+            .add_state::<LevelState>()
             .add_loading_state(
                 LoadingState::new(LevelState::Loading).continue_to_state(LevelState::Loaded),
             )
             .add_collection_to_loading_state::<_, LevelAssets>(LevelState::Loading)
+            .add_collection_to_loading_state::<_, EnemyAssets>(LevelState::Loading)
             .add_system(setup.in_schedule(OnEnter(LevelState::Loaded)))
-            .add_system(change_level_input.in_set(OnUpdate(LevelState::Loaded)))
+            .init_resource::<CurrentLevel>()
+            .add_system(
+                change_level_input
+                    .in_set(OnUpdate(LevelState::Loaded))
+                    .in_set(ChangeLevelSet),
+            )
+            .add_system(
+                restart_on_player_death
+                    .before(change_level_input)
+                    .in_set(OnUpdate(LevelState::Loaded)),
+            )
             .add_system(setup_level_data)
             .add_system(get_path_data)
+            .add_system(build_level_colliders)
             .add_plugin(SkyboxPlugin)
-            .add_system(load_skybox.in_schedule(OnEnter(SkyboxState::Loaded)))
+            .add_plugin(EnvironmentPlugin)
+            // until a level environment is loaded, restored on teardown
             .insert_resource(AmbientLight {
                 color: Color::WHITE,
                 brightness: 1.0,
@@ -109,6 +157,7 @@ fn setup(
 
 fn setup_level_data(
     mut commands: Commands,
+    asset_server: Res<AssetServer>,
     q: Query<(Entity, &Transform, &GltfExtras, Option<&Children>)>,
 ) {
     for (entity, transform, extra, children) in q.iter() {
@@ -121,10 +170,44 @@ fn setup_level_data(
                     for child in children.iter() {
                         println!("Child: {:?}", child);
                         commands.entity(*child).insert(PlayerPathRaw {
-                            parent_transform: transform.clone(),
+                            parent_transform: *transform,
+                            name: None,
                         }); // the nurbs path must be a child or else it will not work
                     }
                 }
+            } else if let Some(name) = extras_tag_value(&extra.value, "PATH::ENEMY::") {
+                println!("Enemy Path found: {}", name);
+                if let Some(children) = children {
+                    for child in children.iter() {
+                        commands.entity(*child).insert(PlayerPathRaw {
+                            parent_transform: *transform,
+                            name: Some(name.clone()),
+                        });
+                    }
+                }
+            }
+        }
+        if extra.value.contains("ARENA::BOSS") {
+            println!("Boss Arena found");
+            commands.entity(entity).insert(BossArena::default());
+        }
+        if let Some(enemy) = extras_tag_value(&extra.value, "TURRET::") {
+            println!("Turret found: {}", enemy);
+            commands.entity(entity).insert(TurretMarker { enemy });
+        }
+        // overrides the environment from the level data
+        if let Some(name) = extras_tag_value(&extra.value, "ENVIRONMENT::") {
+            let path = format!("environments/{}.environment.ron", name);
+            commands.insert_resource(ActiveEnvironment::new(asset_server.load(path)));
+        }
+        if extra.value.contains("COLLIDER") {
+            println!("Level Collider found");
+            commands.entity(entity).insert(LevelColliderRaw);
+            // gltf meshes are children of the node holding the extras
+            if let Some(children) = children {
+                for child in children.iter() {
+                    commands.entity(*child).insert(LevelColliderRaw);
+                }
             }
         }
         commands.entity(entity).remove::<GltfExtras>();
@@ -168,13 +251,13 @@ fn get_path_data(
                 if next_index >= path_node_transforms.len() {
                     next_index = 0;
                 }
-                let current_transform = path_node_transforms[i].clone();
-                let next_transform = path_node_transforms[next_index].clone();
+                let current_transform = path_node_transforms[i];
+                let next_transform = path_node_transforms[next_index];
                 let distance =
                     next_transform.transform.translation - current_transform.transform.translation;
                 total_distance += distance.length();
                 if next_index != 0 {
-                    path_node_transforms[next_index].distance_along_path = total_distance.clone();
+                    path_node_transforms[next_index].distance_along_path = total_distance;
                 }
 
                 path_node_transforms[i]
@@ -189,6 +272,14 @@ fn get_path_data(
             path_length: total_distance,
         });
         commands.entity(entity).remove::<PlayerPathRaw>();
+        // remove the mesh component so that it doesn't render
+        commands.entity(entity).remove::<Handle<Mesh>>();
+
+        // named paths are only used by enemies, they do not spawn the player
+        if let Some(name) = &raw_path.name {
+            commands.entity(entity).insert(NamedPath(name.clone()));
+            continue;
+        }
 
         let spawn: Transform = path_node_transforms
             .first()
@@ -197,19 +288,45 @@ fn get_path_data(
                 distance_along_path: 0.0,
             })
             .unwrap()
-            .clone()
             .transform;
 
         ev_spawn.send(SpawnPlayerEvent { transform: spawn });
+    }
+}
 
-        // remove the mesh component so that it doesn't render
-        commands.entity(entity).remove::<Handle<Mesh>>();
+/// build static trimesh colliders for level geometry tagged with COLLIDER
+fn build_level_colliders(
+    mut commands: Commands,
+    q: Query<(Entity, Option<&Handle<Mesh>>), With<LevelColliderRaw>>,
+    meshes: Res<Assets<Mesh>>,
+) {
+    for (entity, mesh) in q.iter() {
+        let Some(mesh) = mesh else {
+            // the tagged node itself has no mesh, only its children do
+            commands.entity(entity).remove::<LevelColliderRaw>();
+            continue;
+        };
+        let Some(mesh) = meshes.get(mesh) else {
+            continue;
+        };
+        if let Some(collider) = Collider::from_bevy_mesh(mesh, &ComputedColliderShape::TriMesh) {
+            commands.entity(entity).insert((collider, terrain_groups()));
+        }
+        commands.entity(entity).remove::<LevelColliderRaw>();
     }
 }
 
-// change the skybox image
-fn load_skybox(cubemap: ResMut<Cubemap>, asset_server: Res<AssetServer>) {
-    set_skybox_texture(cubemap, asset_server.load("textures/sky.png"));
+/// read the value following a tag in gltf extras, ex: "PATH::ENEMY::weave" -> "weave"
+pub fn extras_tag_value(extras: &str, tag: &str) -> Option<String> {
+    let start = extras.find(tag)? + tag.len();
+    let value: String = extras[start..]
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_')
+        .collect();
+    if value.is_empty() {
+        return None;
+    }
+    Some(value)
 }
 
 // change level based on index
@@ -222,18 +339,30 @@ fn load_level(
     mut ev_spawn: EventWriter<SpawnPlayerEvent>,
 ) {
     let level: Handle<Scene>;
+    let waves: Handle<WaveScript>;
+    let environment: Handle<LevelEnvironment>;
     match index {
         0 => {
             level = level_assets.lv_test.clone();
+            waves = level_assets.lv_test_waves.clone();
+            environment = level_assets.lv_test_environment.clone();
         }
         1 => {
             level = level_assets.lv_1.clone();
+            waves = level_assets.lv_1_waves.clone();
+            environment = level_assets.lv_1_environment.clone();
         }
         _ => {
             level = level_assets.lv_test.clone();
+            waves = level_assets.lv_test_waves.clone();
+            environment = level_assets.lv_test_environment.clone();
         }
     }
 
+    // restart the wave script for the new level
+    commands.insert_resource(WaveDirector::new(waves));
+    commands.insert_resource(ActiveEnvironment::new(environment));
+
     commands.spawn((
         SceneBundle {
             scene: level.clone(),
@@ -259,23 +388,48 @@ fn change_level_input(
     ev_spawn: EventWriter<SpawnPlayerEvent>,
     mut ev_teardown: EventWriter<TeardownLevelEvent>,
     q: Query<Entity, With<Level>>,
+    mut current: ResMut<CurrentLevel>,
 ) {
+    // held ctrl keeps the number keys free for weapons
+    let ctrl = keyboard_input.any_pressed([KeyCode::LControl, KeyCode::RControl]);
     let selection: Option<usize>;
-    if keyboard_input.just_pressed(KeyCode::Key0) {
+    if current.reload {
+        selection = Some(current.index);
+    } else if !ctrl {
+        selection = None;
+    } else if keyboard_input.just_pressed(KeyCode::Key0) {
         selection = Some(0);
     } else if keyboard_input.just_pressed(KeyCode::Key1) {
         selection = Some(1);
     } else {
         selection = None;
     }
-    match selection {
-        Some(scene_index) => {
-            for entity in q.iter() {
-                commands.entity(entity).despawn_recursive();
-            }
-            ev_teardown.send(TeardownLevelEvent {});
-            load_level(commands, level_assets, scene_index, ev_spawn)
+    if let Some(scene_index) = selection {
+        for entity in q.iter() {
+            commands.entity(entity).despawn_recursive();
         }
-        None => {}
+        ev_teardown.send(TeardownLevelEvent {});
+        current.index = scene_index;
+        current.restart = None;
+        current.reload = false;
+        load_level(commands, level_assets, scene_index, ev_spawn)
+    }
+}
+
+/// reload the current level a moment after the player is destroyed
+fn restart_on_player_death(
+    mut ev_destroyed: EventReader<PlayerDestroyedEvent>,
+    mut current: ResMut<CurrentLevel>,
+    time: Res<Time>,
+) {
+    if ev_destroyed.iter().last().is_some() && current.restart.is_none() {
+        current.restart = Some(Timer::from_seconds(RESTART_DELAY, TimerMode::Once));
+    }
+    let Some(timer) = current.restart.as_mut() else {
+        return;
+    };
+    if timer.tick(time.delta()).finished() {
+        current.restart = None;
+        current.reload = true;
     }
 }
@@ -0,0 +1,64 @@
+use bevy::prelude::*;
+
+use crate::pool::PoolCommandsExt;
+
+pub struct LifetimePlugin;
+
+impl Plugin for LifetimePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(expire_lifetimes)
+            .add_system(expire_max_range);
+    }
+}
+
+/// Despawn after a number of seconds, for projectiles and effects
+#[derive(Component)]
+pub struct Lifetime {
+    timer: Timer,
+}
+
+impl Lifetime {
+    pub fn new(seconds: f32) -> Self {
+        Self {
+            timer: Timer::from_seconds(seconds, TimerMode::Once),
+        }
+    }
+
+    /// start counting down again from the full lifetime
+    pub fn reset(&mut self) {
+        self.timer.reset();
+    }
+}
+
+/// Despawn once further than range from where it started
+#[derive(Component)]
+pub struct MaxRange {
+    pub origin: Vec3,
+    pub range: f32,
+}
+
+impl MaxRange {
+    pub fn new(origin: Vec3, range: f32) -> Self {
+        Self { origin, range }
+    }
+}
+
+fn expire_lifetimes(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Lifetime)>,
+    time: Res<Time>,
+) {
+    for (entity, mut lifetime) in query.iter_mut() {
+        if lifetime.timer.tick(time.delta()).finished() {
+            commands.despawn_pooled(entity);
+        }
+    }
+}
+
+fn expire_max_range(mut commands: Commands, query: Query<(Entity, &GlobalTransform, &MaxRange)>) {
+    for (entity, global_transform, max_range) in query.iter() {
+        if global_transform.translation().distance(max_range.origin) > max_range.range {
+            commands.despawn_pooled(entity);
+        }
+    }
+}
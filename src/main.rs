@@ -1,15 +1,39 @@
+mod behaviour;
+#[cfg(test)]
+mod bench;
+mod boss;
 mod bullet;
 mod constants;
+mod cubemap;
+mod debris;
+mod difficulty;
+mod dust;
+mod effect_library;
 mod enemy;
+mod enemy_registry;
+mod enemy_weapon;
+mod environment;
 mod events;
+mod faction;
+mod formation;
 mod game;
+mod health;
 mod level;
+mod lifetime;
 mod materials;
 mod math;
 mod particles;
 mod player;
+mod pool;
+mod rail;
+mod rng;
+mod score;
 mod skybox;
+mod thruster;
+mod turret;
 mod velocity;
+mod waves;
+mod weapon;
 
 use bevy::prelude::*;
 use bevy::window::{PresentMode, WindowResolution};
@@ -28,3 +28,38 @@ pub fn move_toward_f32(from: f32, to: f32, delta: f32) -> f32 {
 pub fn deg_to_rad(degrees: f32) -> f32 {
     degrees * std::f32::consts::PI / 180.0
 }
+
+/// get the point to aim at so a projectile with the given speed intercepts a moving target,
+/// falls back to the current target position if it can not be reached
+pub fn lead_target(shooter: Vec3, target: Vec3, target_velocity: Vec3, speed: f32) -> Vec3 {
+    let offset = target - shooter;
+    // solve |offset + target_velocity * t| = speed * t for the smallest positive t
+    let a = target_velocity.dot(target_velocity) - speed * speed;
+    let b = 2.0 * offset.dot(target_velocity);
+    let c = offset.dot(offset);
+
+    let time = if a.abs() < f32::EPSILON {
+        if b.abs() < f32::EPSILON {
+            return target;
+        }
+        -c / b
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return target;
+        }
+        let root = discriminant.sqrt();
+        let t1 = (-b - root) / (2.0 * a);
+        let t2 = (-b + root) / (2.0 * a);
+        if t1 > 0.0 && t2 > 0.0 {
+            t1.min(t2)
+        } else {
+            t1.max(t2)
+        }
+    };
+
+    if time <= 0.0 {
+        return target;
+    }
+    target + target_velocity * time
+}
@@ -1,80 +1,222 @@
 use bevy::prelude::*;
+use bevy::utils::HashMap;
 use bevy_hanabi::prelude::*;
+use std::collections::VecDeque;
 
-use crate::events::ExplosionEvent;
+use crate::effect_library::{EffectLibrary, EffectLibraryLoader};
+use crate::events::{EffectEvent, EffectKind, SpawnEffectEvent};
+
+pub const IMPACT_EFFECT: &str = "impact";
+pub const SHIELD_HIT_EFFECT: &str = "shield_hit";
+pub const EXPLOSION_EFFECT: &str = "explosion";
+/// the size the explosion effect is made for, bigger wrecks scale it up
+const EXPLOSION_SIZE: f32 = 2.5;
 
 pub struct ParticlePlugin;
 
 impl Plugin for ParticlePlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(HanabiPlugin)
-            .add_startup_system(setup_fireworks)
-            .add_system(handle_explosion_events);
+            .add_asset::<EffectLibrary>()
+            .init_asset_loader::<EffectLibraryLoader>()
+            .init_resource::<Effects>()
+            .add_startup_system(load_effect_library)
+            .add_system(build_effects)
+            .add_system(handle_effect_events.before(play_effects))
+            .add_system(play_effects)
+            .add_system(finish_effects);
     }
 }
 
+/// A playing or pooled instance of a named effect
 #[derive(Component)]
-pub struct Firework;
-
-fn setup_fireworks(mut commands: Commands, mut effects: ResMut<Assets<EffectAsset>>) {
-    let mut color_gradient1 = Gradient::new();
-    color_gradient1.add_key(0.0, Vec4::new(4.0, 4.0, 4.0, 1.0));
-    color_gradient1.add_key(0.1, Vec4::new(4.0, 4.0, 0.0, 1.0));
-    color_gradient1.add_key(0.9, Vec4::new(4.0, 0.0, 0.0, 1.0));
-    color_gradient1.add_key(1.0, Vec4::new(4.0, 0.0, 0.0, 0.0));
-
-    let mut size_gradient1 = Gradient::new();
-    size_gradient1.add_key(0.0, Vec2::splat(0.1));
-    size_gradient1.add_key(0.3, Vec2::splat(0.1));
-    size_gradient1.add_key(1.0, Vec2::splat(0.0));
-
-    let firework_fx = effects.add(
-        EffectAsset {
-            name: "firework".to_string(),
-            capacity: 32768,
-            spawner: Spawner::once(2500.0.into(), false),
-            ..Default::default()
+pub struct EffectInstance {
+    name: String,
+    timer: Timer,
+}
+
+struct EffectPool {
+    effect: Handle<EffectAsset>,
+    duration: f32,
+    max_instances: usize,
+    free: Vec<Entity>,
+    /// oldest first
+    active: VecDeque<Entity>,
+}
+
+/// Effects from the effect library by name, each with a pool of instances
+#[derive(Resource, Default)]
+pub struct Effects {
+    library: Handle<EffectLibrary>,
+    pools: HashMap<String, EffectPool>,
+}
+
+impl Effects {
+    /// the built effect asset, for effects that are owned by an entity instead of pooled
+    pub fn handle(&self, name: &str) -> Option<Handle<EffectAsset>> {
+        self.pools.get(name).map(|pool| pool.effect.clone())
+    }
+}
+
+fn load_effect_library(mut effects: ResMut<Effects>, asset_server: Res<AssetServer>) {
+    effects.library = asset_server.load("effects/library.effects.ron");
+}
+
+/// build the effect assets whenever the library is loaded or changed on disk
+fn build_effects(
+    mut commands: Commands,
+    mut effects: ResMut<Effects>,
+    libraries: Res<Assets<EffectLibrary>>,
+    mut effect_assets: ResMut<Assets<EffectAsset>>,
+    mut ev_library: EventReader<AssetEvent<EffectLibrary>>,
+) {
+    for ev in ev_library.iter() {
+        let (AssetEvent::Created { handle } | AssetEvent::Modified { handle }) = ev else {
+            continue;
+        };
+        if *handle != effects.library {
+            continue;
+        }
+        let Some(library) = libraries.get(handle) else {
+            continue;
+        };
+
+        // instances of the old definitions are thrown away
+        for pool in effects.pools.values() {
+            for entity in pool.free.iter().chain(pool.active.iter()) {
+                commands.entity(*entity).despawn_recursive();
+            }
+        }
+        effects.pools = library
+            .effects
+            .iter()
+            .map(|(name, definition)| {
+                let pool = EffectPool {
+                    effect: effect_assets.add(definition.build(name)),
+                    duration: definition.duration,
+                    max_instances: definition.max_instances,
+                    free: Vec::new(),
+                    active: VecDeque::new(),
+                };
+                (name.clone(), pool)
+            })
+            .collect();
+        info!("Effects loaded: {}", effects.pools.len());
+    }
+}
+
+/// pick the library effect for each kind, its local +z faces along the normal
+fn handle_effect_events(
+    mut ev_effect: EventReader<EffectEvent>,
+    mut ev_spawn_effect: EventWriter<SpawnEffectEvent>,
+) {
+    for ev in ev_effect.iter() {
+        let (name, scale) = match ev.kind {
+            // sparks are the same size whatever they hit
+            EffectKind::Impact => (IMPACT_EFFECT, 1.0),
+            // the ripple wraps the hit ship
+            EffectKind::ShieldHit => (SHIELD_HIT_EFFECT, ev.size),
+            EffectKind::Destruction => (EXPLOSION_EFFECT, ev.size / EXPLOSION_SIZE),
+        };
+        let normal = ev.normal.try_normalize().unwrap_or(Vec3::Y);
+        let up = if normal.y.abs() > 0.99 {
+            Vec3::X
+        } else {
+            Vec3::Y
+        };
+        ev_spawn_effect.send(SpawnEffectEvent {
+            name: name.to_string(),
+            transform: Transform::from_translation(ev.position)
+                .looking_to(-normal, up)
+                .with_scale(Vec3::splat(scale)),
+        });
+    }
+}
+
+/// start an instance of the named effect, reusing a finished one when possible
+fn play_effects(
+    mut commands: Commands,
+    mut effects: ResMut<Effects>,
+    mut query_effect: Query<(
+        &mut Transform,
+        &mut Visibility,
+        &mut EffectInstance,
+        Option<&mut EffectSpawner>,
+    )>,
+    mut ev_effect: EventReader<SpawnEffectEvent>,
+) {
+    for ev in ev_effect.iter() {
+        let Some(pool) = effects.pools.get_mut(&ev.name) else {
+            continue;
+        };
+
+        let instance = match pool.free.pop() {
+            Some(instance) => instance,
+            None if pool.active.len() < pool.max_instances => {
+                // new instances start playing as soon as they spawn
+                let instance = commands
+                    .spawn((
+                        ParticleEffectBundle {
+                            effect: ParticleEffect::new(pool.effect.clone()),
+                            transform: ev.transform,
+                            ..default()
+                        },
+                        EffectInstance {
+                            name: ev.name.clone(),
+                            timer: Timer::from_seconds(pool.duration, TimerMode::Once),
+                        },
+                    ))
+                    .id();
+                pool.active.push_back(instance);
+                continue;
+            }
+            // at the cap, restart the oldest instance here
+            None => match pool.active.pop_front() {
+                Some(instance) => instance,
+                None => continue,
+            },
+        };
+
+        pool.active.push_back(instance);
+        let Ok((mut transform, mut visibility, mut effect_instance, effect_spawner)) =
+            query_effect.get_mut(instance)
+        else {
+            // spawned earlier this frame so not queryable yet, replace what it was spawned with
+            commands.entity(instance).insert((
+                ParticleEffect::new(pool.effect.clone()),
+                ev.transform,
+                Visibility::Inherited,
+                EffectInstance {
+                    name: ev.name.clone(),
+                    timer: Timer::from_seconds(pool.duration, TimerMode::Once),
+                },
+            ));
+            continue;
+        };
+        *transform = ev.transform;
+        *visibility = Visibility::Inherited;
+        effect_instance.timer.reset();
+        // hanabi adds the spawner on the instance's first tick, until then it hasn't played
+        if let Some(mut effect_spawner) = effect_spawner {
+            effect_spawner.reset();
         }
-        .init(InitPositionSphereModifier {
-            center: Vec3::ZERO,
-            radius: 2.,
-            dimension: ShapeDimension::Volume,
-        })
-        .init(InitVelocitySphereModifier {
-            center: Vec3::ZERO,
-            // Give a bit of variation by randomizing the initial speed
-            speed: Value::Uniform((65., 75.)),
-        })
-        .init(InitLifetimeModifier {
-            // Give a bit of variation by randomizing the lifetime per particle
-            lifetime: Value::Uniform((0.8, 1.2)),
-        })
-        .init(InitAgeModifier {
-            // Give a bit of variation by randomizing the age per particle. This will control the
-            // starting color and starting size of particles.
-            age: Value::Uniform((0.0, 0.2)),
-        })
-        .update(LinearDragModifier { drag: 5. })
-        .update(AccelModifier::constant(Vec3::new(0., -8., 0.)))
-        .render(ColorOverLifetimeModifier {
-            gradient: color_gradient1,
-        })
-        .render(SizeOverLifetimeModifier {
-            gradient: size_gradient1,
-        }),
-    );
-
-    commands.spawn((ParticleEffectBundle::new(firework_fx), Firework));
+    }
 }
 
-fn handle_explosion_events(
-    mut query_effect: Query<(&mut ParticleEffect, &mut Transform), With<Firework>>,
-    mut ev_explosion: EventReader<ExplosionEvent>,
+/// hide finished effects and return them to their pool
+fn finish_effects(
+    mut effects: ResMut<Effects>,
+    mut query_effect: Query<(Entity, &mut EffectInstance, &mut Visibility)>,
+    time: Res<Time>,
 ) {
-    for (mut firework_fx, mut firework_transform) in query_effect.iter_mut() {
-        for ev in ev_explosion.iter() {
-            firework_transform.translation = ev.0.translation;
-            firework_fx.maybe_spawner().unwrap().reset();
+    for (entity, mut effect_instance, mut visibility) in query_effect.iter_mut() {
+        if !effect_instance.timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+        *visibility = Visibility::Hidden;
+        if let Some(pool) = effects.pools.get_mut(&effect_instance.name) {
+            pool.active.retain(|active| *active != entity);
+            pool.free.push(entity);
         }
     }
 }
@@ -1,11 +1,17 @@
 use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
 
+use crate::bullet::ProjectileKind;
 use crate::constants::{make_cam_entity, BOUNDS_POS};
 use crate::events::{SpawnBulletEvent, SpawnPlayerEvent};
-use crate::level::PlayerPath;
+use crate::faction::Faction;
+use crate::health::{Health, InvulnerabilityWindow, Shield};
+use crate::level::{NamedPath, PlayerPath};
 use crate::materials::ColorMaterial;
 use crate::math::{deg_to_rad, move_toward, move_toward_f32};
-use crate::velocity::Velocity;
+use crate::thruster::ThrusterMount;
+use crate::velocity::{MeasuredVelocity, Velocity};
+use crate::weapon::Weapon;
 
 pub struct PlayerPlugin;
 
@@ -13,6 +19,8 @@ impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.add_system(on_spawn_player)
             .add_system(move_player)
+            .add_system(boost)
+            .add_system(select_projectile)
             .add_system(fire_bullet)
             .add_system(move_along_path)
             .add_plugin(MaterialPlugin::<ColorMaterial>::default());
@@ -20,9 +28,27 @@ impl Plugin for PlayerPlugin {
 }
 
 const MAX_SPEED: f32 = 30.0;
+const RAIL_SPEED: f32 = 20.0;
+const BOOST_MULTIPLIER: f32 = 2.0;
 const ROT_SPEED: f32 = 3.0;
 const ACCELERATION: f32 = 0.75;
 const BULLET_SPEED: f32 = 300.0;
+const BULLET_DAMAGE: f32 = 1.0;
+const SPREAD_COUNT: u32 = 5;
+const SPREAD_ANGLE: f32 = 20.0;
+const MISSILE_SPEED: f32 = 150.0;
+const MISSILE_TURN_RATE: f32 = 4.0;
+const BEAM_LENGTH: f32 = 250.0;
+const BEAM_DAMAGE_PER_SECOND: f32 = 8.0;
+const FIRE_RATE: f32 = 10.0;
+const HEAT_PER_SHOT: f32 = 0.08;
+const COOLING_RATE: f32 = 0.5;
+const RECOVER_HEAT: f32 = 0.3;
+const HEALTH: f32 = 10.0;
+const SHIELD: f32 = 5.0;
+const SHIELD_RECHARGE_RATE: f32 = 1.0;
+const SHIELD_RECHARGE_DELAY: f32 = 3.0;
+const INVULNERABILITY_TIME: f32 = 0.5;
 
 #[derive(Component)]
 pub struct Player;
@@ -30,26 +56,40 @@ pub struct Player;
 #[derive(Component)]
 pub struct PlayerRoot;
 
+/// the camera following the player, as opposed to ex: an editor camera
+#[derive(Component)]
+pub struct PlayerCamera;
+
+/// Move faster along the rail while held
+#[derive(Component, Default)]
+pub struct Boost {
+    pub active: bool,
+}
+
 #[derive(Component)]
 pub struct PathFollower {
-    pub index: usize,
     pub distance_along_path: f32,
+    /// stop moving along the path, ex: while a boss is alive
+    pub hold: bool,
 }
 
+/// top level entities of the last player, ex: its root, or a ship or camera placed in the level
+type StalePlayer = (
+    Without<Parent>,
+    Or<(With<PlayerRoot>, With<Player>, With<Camera>)>,
+);
+
 fn on_spawn_player(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     asset_server: Res<AssetServer>,
     mut ev_spawn: EventReader<SpawnPlayerEvent>,
-    q: Query<Entity, With<Player>>,
-    q_camera: Query<Entity, With<Camera>>,
+    q_stale: Query<Entity, StalePlayer>,
 ) {
     for ev in ev_spawn.iter() {
-        for entity in q.iter() {
-            commands.entity(entity).despawn_recursive();
-        }
-        for entity in q_camera.iter() {
+        // the ship and its camera go with the root, a stale root would keep following the rail
+        for entity in q_stale.iter() {
             commands.entity(entity).despawn_recursive();
         }
 
@@ -58,7 +98,7 @@ fn on_spawn_player(
 
         let offset = 15.0;
 
-        let mut root_transform = ev.transform.clone();
+        let mut root_transform = ev.transform;
         root_transform.translation += Vec3::Z * offset;
 
         let mut ship_transform = Transform::IDENTITY;
@@ -68,26 +108,40 @@ fn on_spawn_player(
             .spawn((
                 PlayerRoot,
                 PathFollower {
-                    index: 0,
                     distance_along_path: 0.0,
+                    hold: false,
                 },
                 Velocity(Vec3::ZERO),
                 SceneBundle {
-                    transform: root_transform.clone(),
+                    transform: root_transform,
                     ..default()
                 },
             ))
             .with_children(|root| {
-                root.spawn(make_cam_entity(Transform::IDENTITY));
+                root.spawn((make_cam_entity(Transform::IDENTITY), PlayerCamera));
                 root.spawn((
                     SceneBundle {
                         scene: asset_server.load("models/Spaceship/player.gltf#Scene0"),
-                        transform: ship_transform.clone(),
+                        transform: ship_transform,
                         ..default()
                     },
                     Player,
                     Velocity(Vec3::ZERO),
+                    Weapon::new(FIRE_RATE, HEAT_PER_SHOT, COOLING_RATE, RECOVER_HEAT),
+                    ProjectileKind::Bolt,
+                    Faction::Player,
+                    Health::new(HEALTH),
+                    Shield::new(SHIELD, SHIELD_RECHARGE_RATE, SHIELD_RECHARGE_DELAY),
+                    InvulnerabilityWindow(INVULNERABILITY_TIME),
+                    MeasuredVelocity::default(),
+                    Collider::cuboid(2.0, 0.75, 2.0),
+                    Faction::Player.body_groups(),
+                    RigidBody::KinematicPositionBased,
+                    ActiveEvents::COLLISION_EVENTS,
+                    // bullets have no rigid body, so they count as static colliders
+                    ActiveCollisionTypes::default() | ActiveCollisionTypes::KINEMATIC_STATIC,
                 ))
+                .insert((Boost::default(), ThrusterMount::new(Vec3::Z * 2.0)))
                 .with_children(|ship| {
                     // crosshair1
                     ship.spawn(MaterialMeshBundle {
@@ -163,35 +217,90 @@ fn move_player(
     }
 }
 
+// boost while shift is held
+// @TODO: Use an input map
+fn boost(keyboard_input: Res<Input<KeyCode>>, mut query: Query<&mut Boost, With<Player>>) {
+    for mut boost in query.iter_mut() {
+        boost.active = keyboard_input.pressed(KeyCode::LShift);
+    }
+}
+
 // move the player along the path, this is stored in a player path component
 fn move_along_path(
     mut query: Query<(&mut Transform, &mut PathFollower), With<PlayerRoot>>,
-    mut path_query: Query<&PlayerPath>,
+    mut path_query: Query<&PlayerPath, Without<NamedPath>>,
+    boost_query: Query<&Boost, With<Player>>,
     time: Res<Time>,
 ) {
-    for (mut root_transform, mut path_follower) in query.iter_mut() {
-        for path in path_query.iter_mut() {
-            path_follower.distance_along_path = (path_follower.distance_along_path
-                + 20.0 * time.delta_seconds())
-                % path.path_length;
-            let next_transform = path.lerp_next_transform(path_follower.distance_along_path);
-            root_transform.translation = next_transform.translation;
-            root_transform.rotation = next_transform.rotation;
+    let speed = match boost_query.iter().next() {
+        Some(boost) if boost.active => RAIL_SPEED * BOOST_MULTIPLIER,
+        _ => RAIL_SPEED,
+    };
+    let Ok((mut root_transform, mut path_follower)) = query.get_single_mut() else {
+        return;
+    };
+    if path_follower.hold {
+        return;
+    }
+    for path in path_query.iter_mut() {
+        path_follower.distance_along_path =
+            (path_follower.distance_along_path + speed * time.delta_seconds()) % path.path_length;
+        let next_transform = path.lerp_next_transform(path_follower.distance_along_path);
+        root_transform.translation = next_transform.translation;
+        root_transform.rotation = next_transform.rotation;
+    }
+}
+
+// switch weapons with the number keys
+// @TODO: Use an input map
+fn select_projectile(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut query: Query<&mut ProjectileKind, With<Player>>,
+) {
+    // ctrl + number switches level instead
+    if keyboard_input.any_pressed([KeyCode::LControl, KeyCode::RControl]) {
+        return;
+    }
+    let kind = if keyboard_input.just_pressed(KeyCode::Key1) {
+        ProjectileKind::Bolt
+    } else if keyboard_input.just_pressed(KeyCode::Key2) {
+        ProjectileKind::Spread {
+            count: SPREAD_COUNT,
+            angle: SPREAD_ANGLE,
         }
+    } else if keyboard_input.just_pressed(KeyCode::Key3) {
+        ProjectileKind::Missile {
+            turn_rate: MISSILE_TURN_RATE,
+        }
+    } else if keyboard_input.just_pressed(KeyCode::Key4) {
+        ProjectileKind::Beam {
+            length: BEAM_LENGTH,
+            damage_per_second: BEAM_DAMAGE_PER_SECOND,
+        }
+    } else {
+        return;
+    };
+
+    for mut projectile in query.iter_mut() {
+        *projectile = kind;
     }
 }
 
 fn fire_bullet(
     keyboard_input: Res<Input<KeyCode>>,
-    query: Query<&GlobalTransform, With<Player>>,
+    mut query: Query<(Entity, &GlobalTransform, &mut Weapon, &ProjectileKind), With<Player>>,
     mut ev_fire: EventWriter<SpawnBulletEvent>,
 ) {
-    // if spacebar is pressed
-    if !keyboard_input.just_pressed(KeyCode::Space) {
+    // auto fire while spacebar is held
+    if !keyboard_input.pressed(KeyCode::Space) {
         return;
     }
 
-    for player_global_tranform in query.iter() {
+    for (entity, player_global_tranform, mut weapon, kind) in query.iter_mut() {
+        if !weapon.try_fire() {
+            continue;
+        }
+
         let bullet_transform = Transform {
             translation: player_global_tranform.translation()
                 + player_global_tranform.forward() * 2.0,
@@ -200,9 +309,16 @@ fn fire_bullet(
         };
 
         ev_fire.send(SpawnBulletEvent {
+            kind: *kind,
+            shooter: entity,
             transform: bullet_transform,
             direction: player_global_tranform.forward(),
-            speed: BULLET_SPEED,
+            speed: match kind {
+                ProjectileKind::Missile { .. } => MISSILE_SPEED,
+                _ => BULLET_SPEED,
+            },
+            faction: Faction::Player,
+            damage: BULLET_DAMAGE,
         });
     }
 }
@@ -0,0 +1,212 @@
+use bevy::ecs::system::Command;
+use bevy::hierarchy::despawn_with_children_recursive;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::enemy_registry::{EnemyAssets, EnemyRegistry};
+use crate::health::clear_hit_flash;
+use crate::level::LevelState;
+
+pub const BULLET_MODEL: &str = "models/Spaceship/bullet.gltf#Scene0";
+const BULLET_POOL_SIZE: usize = 128;
+const ENEMY_POOL_SIZE: usize = 16;
+
+pub struct PoolPlugin;
+
+impl Plugin for PoolPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EntityPools>()
+            .add_system(prewarm_pools.in_schedule(OnEnter(LevelState::Loaded)))
+            .add_system(log_pool_stats);
+    }
+}
+
+/// A scene instance owned by a pool, it is parented to the entity using it
+/// and hidden when returned to the pool
+#[derive(Component)]
+pub struct PooledModel {
+    scene: String,
+}
+
+#[derive(Default, Debug, Clone, Copy)]
+pub struct PoolStats {
+    /// scene instances spawned, including prewarmed ones
+    pub created: usize,
+    /// times an instance was taken from the pool instead of spawned
+    pub reused: usize,
+    pub active: usize,
+    pub peak_active: usize,
+    pub free: usize,
+}
+
+#[derive(Default)]
+struct Pool {
+    free: Vec<Entity>,
+    stats: PoolStats,
+}
+
+/// Pools of scene instances by scene path, so bullets and enemies don't instantiate a scene every spawn
+#[derive(Resource, Default)]
+pub struct EntityPools {
+    pools: HashMap<String, Pool>,
+}
+
+impl EntityPools {
+    /// attach a model to an entity, a new scene instance is only spawned when the pool is empty
+    pub fn attach_model(
+        &mut self,
+        commands: &mut Commands,
+        asset_server: &AssetServer,
+        entity: Entity,
+        scene: &str,
+    ) {
+        let pool = self.pools.entry(scene.to_string()).or_default();
+        let model = match pool.free.pop() {
+            Some(model) => {
+                pool.stats.reused += 1;
+                commands
+                    .entity(model)
+                    .insert((Transform::IDENTITY, Visibility::Inherited));
+                model
+            }
+            None => {
+                pool.stats.created += 1;
+                spawn_model(commands, asset_server, scene, Visibility::Inherited)
+            }
+        };
+        pool.stats.active += 1;
+        pool.stats.peak_active = pool.stats.peak_active.max(pool.stats.active);
+        commands.entity(entity).add_child(model);
+    }
+
+    /// spawn hidden scene instances until the pool holds at least count
+    pub fn prewarm(
+        &mut self,
+        commands: &mut Commands,
+        asset_server: &AssetServer,
+        scene: &str,
+        count: usize,
+    ) {
+        let pool = self.pools.entry(scene.to_string()).or_default();
+        while pool.free.len() + pool.stats.active < count {
+            pool.stats.created += 1;
+            pool.free.push(spawn_model(
+                commands,
+                asset_server,
+                scene,
+                Visibility::Hidden,
+            ));
+        }
+    }
+
+    fn release(&mut self, model: Entity, scene: &str) {
+        let pool = self.pools.entry(scene.to_string()).or_default();
+        pool.stats.active = pool.stats.active.saturating_sub(1);
+        pool.free.push(model);
+    }
+
+    pub fn stats(&self) -> impl Iterator<Item = (&str, PoolStats)> {
+        self.pools.iter().map(|(scene, pool)| {
+            let mut stats = pool.stats;
+            stats.free = pool.free.len();
+            (scene.as_str(), stats)
+        })
+    }
+}
+
+fn spawn_model(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    scene: &str,
+    visibility: Visibility,
+) -> Entity {
+    commands
+        .spawn((
+            SceneBundle {
+                scene: asset_server.load(scene),
+                visibility,
+                ..default()
+            },
+            PooledModel {
+                scene: scene.to_string(),
+            },
+        ))
+        .id()
+}
+
+/// Despawn an entity and its children, pooled models are detached and returned to their pool instead
+pub struct DespawnPooled(pub Entity);
+
+impl Command for DespawnPooled {
+    fn write(self, world: &mut World) {
+        if world.get_entity(self.0).is_none() {
+            return;
+        }
+
+        let mut models = Vec::new();
+        let mut stack = vec![self.0];
+        while let Some(entity) = stack.pop() {
+            if let Some(model) = world.get::<PooledModel>(entity) {
+                models.push((entity, model.scene.clone()));
+                continue;
+            }
+            if let Some(children) = world.get::<Children>(entity) {
+                stack.extend(children.iter().copied());
+            }
+        }
+
+        for (model, scene) in models {
+            // a model released mid flash would come back tinted
+            clear_hit_flash(world, model);
+            world
+                .entity_mut(model)
+                .remove_parent()
+                .insert(Visibility::Hidden);
+            world.resource_mut::<EntityPools>().release(model, &scene);
+        }
+
+        despawn_with_children_recursive(world, self.0);
+    }
+}
+
+pub trait PoolCommandsExt {
+    /// use instead of despawn_recursive for anything that may hold a pooled model
+    fn despawn_pooled(&mut self, entity: Entity);
+}
+
+impl PoolCommandsExt for Commands<'_, '_> {
+    fn despawn_pooled(&mut self, entity: Entity) {
+        self.add(DespawnPooled(entity));
+    }
+}
+
+fn prewarm_pools(
+    mut commands: Commands,
+    mut pools: ResMut<EntityPools>,
+    asset_server: Res<AssetServer>,
+    enemy_assets: Res<EnemyAssets>,
+    registries: Res<Assets<EnemyRegistry>>,
+) {
+    pools.prewarm(&mut commands, &asset_server, BULLET_MODEL, BULLET_POOL_SIZE);
+    if let Some(registry) = registries.get(&enemy_assets.registry) {
+        for archetype in registry.types.values() {
+            pools.prewarm(
+                &mut commands,
+                &asset_server,
+                &archetype.model,
+                ENEMY_POOL_SIZE,
+            );
+        }
+    }
+}
+
+// log pool usage, use it to tune the prewarm sizes
+// @TODO: Use an input map
+fn log_pool_stats(keyboard_input: Res<Input<KeyCode>>, pools: Res<EntityPools>) {
+    if !keyboard_input.just_pressed(KeyCode::P) {
+        return;
+    }
+    for (scene, stats) in pools.stats() {
+        debug!("Pool {}: {:?}", scene, stats);
+    }
+}
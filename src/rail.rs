@@ -0,0 +1,190 @@
+use bevy::prelude::*;
+
+use crate::constants::BOUNDS_POS;
+use crate::level::{NamedPath, PlayerPath};
+use crate::player::{PathFollower, Player, PlayerRoot};
+use crate::pool::PoolCommandsExt;
+use crate::velocity::Velocity;
+
+/// how far behind the player rail space entities are kept before being removed
+const RAIL_CULL_BEHIND: f32 = 30.0;
+/// how far ahead of the player entities are kept before being removed
+const RAIL_CULL_AHEAD: f32 = BOUNDS_POS.z * 2.0;
+/// how far to the side of the rail moving world space entities are kept before being removed
+const RAIL_CULL_SIDE: f32 = BOUNDS_POS.z;
+
+pub struct RailPlugin;
+
+impl Plugin for RailPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PlayerRail>()
+            .add_system(update_player_rail)
+            .add_system(move_in_rail_space.after(update_player_rail))
+            .add_system(cull_world_space.after(update_player_rail));
+    }
+}
+
+/// Where the player ship is on the rail, used to place and move things in rail space
+#[derive(Resource, Default)]
+pub struct PlayerRail {
+    /// distance of the player ship along the rail
+    pub distance: f32,
+    /// lateral and vertical offset of the player ship from the rail
+    pub offset: Vec2,
+    pub path_length: Option<f32>,
+    root: Transform,
+    root_distance: f32,
+}
+
+impl PlayerRail {
+    /// signed distance from the player ship to a point on the rail, wraps around looping paths
+    pub fn distance_to(&self, distance: f32) -> f32 {
+        let delta = distance - self.distance;
+        match self.path_length {
+            Some(length) if length > 0.0 => {
+                let delta = delta.rem_euclid(length);
+                if delta > length / 2.0 {
+                    delta - length
+                } else {
+                    delta
+                }
+            }
+            _ => delta,
+        }
+    }
+
+    /// get the world frame of the rail at a distance, forward is -z
+    pub fn frame_at(&self, path: Option<&PlayerPath>, distance: f32) -> Transform {
+        match path {
+            Some(path) if path.path_length > 0.0 => {
+                path.lerp_next_transform(distance.rem_euclid(path.path_length))
+            }
+            // no path in the level, the rail is a straight line through the player root
+            _ => {
+                let mut frame = self.root;
+                frame.translation += frame.forward() * (distance - self.root_distance);
+                frame
+            }
+        }
+    }
+}
+
+/// Position an entity relative to the player rail instead of world space,
+/// its velocity is read in the rail frame (x right, y up, -z further along the rail)
+#[derive(Component, Clone, Copy)]
+pub struct RailSpace {
+    pub distance: f32,
+    pub offset: Vec2,
+    /// rotation relative to the rail frame
+    pub rotation: Quat,
+}
+
+impl RailSpace {
+    pub fn new(distance: f32, offset: Vec2, rotation: Quat) -> Self {
+        Self {
+            distance,
+            offset,
+            rotation,
+        }
+    }
+
+    /// move by a displacement expressed in the rail frame
+    pub fn translate(&mut self, local: Vec3) {
+        self.distance -= local.z;
+        self.offset += local.truncate();
+    }
+
+    pub fn to_world(self, rail: &PlayerRail, path: Option<&PlayerPath>) -> Transform {
+        let frame = rail.frame_at(path, self.distance);
+        Transform {
+            translation: frame.translation + frame.rotation * self.offset.extend(0.0),
+            rotation: frame.rotation * self.rotation,
+            ..default()
+        }
+    }
+}
+
+fn update_player_rail(
+    mut rail: ResMut<PlayerRail>,
+    root_query: Query<(&Transform, &PathFollower), With<PlayerRoot>>,
+    ship_query: Query<&Transform, With<Player>>,
+    path_query: Query<&PlayerPath, Without<NamedPath>>,
+) {
+    rail.path_length = path_query.iter().next().map(|path| path.path_length);
+
+    let Ok((root_transform, path_follower)) = root_query.get_single() else {
+        return;
+    };
+    rail.root = *root_transform;
+    rail.root_distance = path_follower.distance_along_path;
+
+    // the ship is a child of the root, its local z is along the rail
+    let ship = ship_query
+        .iter()
+        .next()
+        .map(|t| t.translation)
+        .unwrap_or(Vec3::ZERO);
+    rail.distance = path_follower.distance_along_path - ship.z;
+    rail.offset = ship.truncate();
+}
+
+/// apply rail frame velocities and place rail space entities in the world
+fn move_in_rail_space(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut RailSpace, &mut Transform, Option<&Velocity>)>,
+    rail: Res<PlayerRail>,
+    path_query: Query<&PlayerPath, Without<NamedPath>>,
+    time: Res<Time>,
+) {
+    let path = path_query.iter().next();
+
+    for (entity, mut rail_space, mut transform, velocity) in query.iter_mut() {
+        if let Some(velocity) = velocity {
+            rail_space.translate(velocity.0 * time.delta_seconds());
+        }
+
+        let world = rail_space.to_world(&rail, path);
+        transform.translation = world.translation;
+        transform.rotation = world.rotation;
+
+        // delete once the player has passed it or it is too far ahead
+        let ahead = rail.distance_to(rail_space.distance);
+        if !(-RAIL_CULL_BEHIND..=RAIL_CULL_AHEAD).contains(&ahead) {
+            commands.despawn_pooled(entity);
+        }
+    }
+}
+
+/// moving world space entities, the player moves with the rail itself
+type WorldSpaceFilter = (
+    With<Velocity>,
+    Without<RailSpace>,
+    Without<Player>,
+    Without<PlayerRoot>,
+);
+
+/// remove moving world space entities that left the area around the player, measured in the rail frame
+fn cull_world_space(
+    mut commands: Commands,
+    query: Query<(Entity, &GlobalTransform), WorldSpaceFilter>,
+    root_query: Query<(), With<PlayerRoot>>,
+    rail: Res<PlayerRail>,
+    path_query: Query<&PlayerPath, Without<NamedPath>>,
+) {
+    if root_query.is_empty() {
+        return;
+    }
+    let frame = rail.frame_at(path_query.iter().next(), rail.distance);
+    let to_local = frame.compute_matrix().inverse();
+
+    for (entity, global_transform) in query.iter() {
+        let local = to_local.transform_point3(global_transform.translation());
+        // the rail frame faces -z
+        let ahead = -local.z;
+        if !(-RAIL_CULL_BEHIND..=RAIL_CULL_AHEAD).contains(&ahead)
+            || local.truncate().length() > RAIL_CULL_SIDE
+        {
+            commands.despawn_pooled(entity);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use crate::events::TeardownLevelEvent;
+
+pub struct RngPlugin;
+
+impl Plugin for RngPlugin {
+    fn build(&self, app: &mut App) {
+        let seed = seed_from_args().unwrap_or_else(|| rand::thread_rng().gen());
+        println!("RNG seed: {}", seed);
+        app.insert_resource(GameRng::new(seed))
+            .add_system(on_teardown);
+    }
+}
+
+/// Independent random streams, so extra randomness in one subsystem does not change another
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RngStream {
+    Spawns,
+    Effects,
+}
+
+/// Seeded source for all gameplay randomness, the same seed gives the same run
+#[derive(Resource)]
+pub struct GameRng {
+    seed: u64,
+    streams: HashMap<RngStream, StdRng>,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            streams: HashMap::default(),
+        }
+    }
+
+    pub fn stream(&mut self, stream: RngStream) -> &mut StdRng {
+        let seed = self.seed;
+        self.streams.entry(stream).or_insert_with(|| {
+            // spread the stream index over the seed so each stream gets a different sequence
+            let salt = (stream as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+            StdRng::seed_from_u64(seed ^ salt)
+        })
+    }
+
+    /// start every stream over from the seed
+    pub fn reset(&mut self) {
+        self.streams.clear();
+    }
+}
+
+/// read the seed from `--seed <n>` or the GAME_SEED environment variable
+fn seed_from_args() -> Option<u64> {
+    let args: Vec<String> = std::env::args().collect();
+    let from_args = args
+        .iter()
+        .position(|arg| arg == "--seed")
+        .and_then(|index| args.get(index + 1))
+        .and_then(|seed| seed.parse().ok());
+    from_args.or_else(|| std::env::var("GAME_SEED").ok()?.parse().ok())
+}
+
+// every level starts from the same state so replays of a level get identical spawns
+fn on_teardown(mut rng: ResMut<GameRng>, mut ev_teardown: EventReader<TeardownLevelEvent>) {
+    for _ in ev_teardown.iter() {
+        rng.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draw(rng: &mut GameRng, stream: RngStream) -> Vec<u32> {
+        (0..8).map(|_| rng.stream(stream).gen()).collect()
+    }
+
+    #[test]
+    fn same_seed_gives_same_spawns() {
+        let mut a = GameRng::new(1234);
+        let mut b = GameRng::new(1234);
+        assert_eq!(
+            draw(&mut a, RngStream::Spawns),
+            draw(&mut b, RngStream::Spawns)
+        );
+    }
+
+    #[test]
+    fn streams_are_independent() {
+        let mut a = GameRng::new(1234);
+        let mut b = GameRng::new(1234);
+        // effects drawn in between must not shift the spawns
+        draw(&mut b, RngStream::Effects);
+        assert_eq!(
+            draw(&mut a, RngStream::Spawns),
+            draw(&mut b, RngStream::Spawns)
+        );
+
+        let mut c = GameRng::new(1234);
+        assert_ne!(
+            draw(&mut c, RngStream::Spawns),
+            draw(&mut c, RngStream::Effects)
+        );
+    }
+
+    #[test]
+    fn reset_starts_over() {
+        let mut rng = GameRng::new(1234);
+        let first = draw(&mut rng, RngStream::Spawns);
+        rng.reset();
+        assert_eq!(first, draw(&mut rng, RngStream::Spawns));
+    }
+}
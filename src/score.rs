@@ -0,0 +1,39 @@
+use bevy::prelude::*;
+
+use crate::events::{EnemyDestroyedEvent, SquadronClearedEvent, TeardownLevelEvent};
+
+pub struct ScorePlugin;
+
+impl Plugin for ScorePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Score>()
+            .add_system(add_kill_score)
+            .add_system(add_squadron_bonus)
+            .add_system(on_teardown);
+    }
+}
+
+/// Points scored in the current level
+#[derive(Resource, Default)]
+pub struct Score {
+    pub total: u32,
+}
+
+fn add_kill_score(mut score: ResMut<Score>, mut ev_destroyed: EventReader<EnemyDestroyedEvent>) {
+    for ev in ev_destroyed.iter() {
+        score.total += ev.score;
+    }
+}
+
+fn add_squadron_bonus(mut score: ResMut<Score>, mut ev_cleared: EventReader<SquadronClearedEvent>) {
+    for ev in ev_cleared.iter() {
+        score.total += ev.bonus;
+    }
+}
+
+// every level starts from zero, a restart after the player is destroyed too
+fn on_teardown(mut score: ResMut<Score>, mut ev_teardown: EventReader<TeardownLevelEvent>) {
+    for _ in ev_teardown.iter() {
+        score.total = 0;
+    }
+}
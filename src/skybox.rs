@@ -1,5 +1,6 @@
 use bevy::{
     asset::LoadState,
+    ecs::system::SystemParam,
     pbr::{MaterialPipeline, MaterialPipelineKey},
     prelude::*,
     reflect::TypeUuid,
@@ -9,37 +10,46 @@ use bevy::{
         render_resource::{
             AsBindGroup, AsBindGroupError, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
             BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType,
-            OwnedBindingResource, PreparedBindGroup, RenderPipelineDescriptor, SamplerBindingType,
-            ShaderRef, ShaderStages, SpecializedMeshPipelineError, TextureSampleType,
-            TextureViewDescriptor, TextureViewDimension,
+            BufferBindingType, BufferInitDescriptor, BufferUsages, OwnedBindingResource,
+            PreparedBindGroup, RenderPipelineDescriptor, SamplerBindingType, ShaderRef,
+            ShaderStages, SpecializedMeshPipelineError, TextureSampleType, TextureViewDimension,
         },
         renderer::RenderDevice,
         texture::FallbackImage,
     },
+    tasks::{AsyncComputeTaskPool, Task},
 };
+use futures_lite::future;
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, States)]
+use crate::cubemap::{equirectangular_to_cube, reinterpret_stacked, stack_faces, CubemapLayout};
+
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash, States)]
 pub enum SkyboxState {
+    #[default]
     Loading,
     Loaded,
 }
 
-impl Default for SkyboxState {
-    fn default() -> Self {
-        Self::Loading
-    }
-}
-
 #[derive(Resource)]
 pub struct Cubemap {
     pub is_loaded: bool,
+    /// the cube texture, only valid once loaded
     pub image_handle: Handle<Image>,
+    pub layout: CubemapLayout,
+    /// the images the cube texture is made from, dropped once it is built
+    pub sources: Vec<Handle<Image>>,
+    /// brightness in stops, 0 leaves the image as is
+    pub exposure: f32,
+    /// panoramas and faces are converted off the main thread
+    conversion: Option<Task<Option<Image>>>,
 }
 
 #[derive(Debug, Clone, TypeUuid)]
 #[uuid = "9509a0f8-3c05-48ee-a13e-a93226c7f488"]
 struct CubemapMaterial {
     base_color_texture: Option<Handle<Image>>,
+    /// multiplies the texture color
+    exposure: f32,
 }
 
 impl Material for CubemapMaterial {
@@ -75,6 +85,16 @@ impl AsBindGroup for CubemapMaterial {
         let image = images
             .get(base_color_texture)
             .ok_or(AsBindGroupError::RetryNextUpdate)?;
+        // uniforms are at least 16 bytes
+        let exposure: Vec<u8> = [self.exposure, 0.0, 0.0, 0.0]
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        let exposure_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("cubemap_exposure_buffer"),
+            contents: &exposure,
+            usage: BufferUsages::UNIFORM,
+        });
         let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
             entries: &[
                 BindGroupEntry {
@@ -85,6 +105,10 @@ impl AsBindGroup for CubemapMaterial {
                     binding: 1,
                     resource: BindingResource::Sampler(&image.sampler),
                 },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: exposure_buffer.as_entire_binding(),
+                },
             ],
             label: Some("cubemap_texture_material_bind_group"),
             layout,
@@ -95,6 +119,7 @@ impl AsBindGroup for CubemapMaterial {
             bindings: vec![
                 OwnedBindingResource::TextureView(image.texture_view.clone()),
                 OwnedBindingResource::Sampler(image.sampler.clone()),
+                OwnedBindingResource::Buffer(exposure_buffer),
             ],
             data: (),
         })
@@ -121,6 +146,17 @@ impl AsBindGroup for CubemapMaterial {
                     ty: BindingType::Sampler(SamplerBindingType::Filtering),
                     count: None,
                 },
+                // Exposure
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
             ],
             label: None,
         })
@@ -134,7 +170,8 @@ impl Plugin for SkyboxPlugin {
         app.add_state::<SkyboxState>()
             .add_plugin(MaterialPlugin::<CubemapMaterial>::default())
             .add_startup_system(setup_skybox)
-            .add_system(load_skybox);
+            .add_system(load_skybox)
+            .add_system(apply_exposure);
     }
 }
 
@@ -142,61 +179,183 @@ fn setup_skybox(mut commands: Commands, asset_server: Res<AssetServer>) {
     let skybox_handle = asset_server.load("textures/space_cubemap.png");
     commands.insert_resource(Cubemap {
         is_loaded: false,
-        image_handle: skybox_handle,
+        image_handle: skybox_handle.clone(),
+        layout: CubemapLayout::Stacked,
+        sources: vec![skybox_handle],
+        exposure: 0.0,
+        conversion: None,
     });
 }
 
-fn load_skybox(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut images: ResMut<Assets<Image>>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut cubemap_materials: ResMut<Assets<CubemapMaterial>>,
-    mut cubemap: ResMut<Cubemap>,
-    cubes: Query<&Handle<CubemapMaterial>>,
-    mut next_sky_state: ResMut<NextState<SkyboxState>>,
-) {
-    if !cubemap.is_loaded
-        && asset_server.get_load_state(cubemap.image_handle.clone_weak()) == LoadState::Loaded
-    {
-        let mut image = images.get_mut(&cubemap.image_handle).unwrap();
-        // NOTE: PNGs do not have any metadata that could indicate they contain a cubemap texture,
-        // so they appear as one texture. The following code reconfigures the texture as necessary.
-        if image.texture_descriptor.array_layer_count() == 1 {
-            image.reinterpret_stacked_2d_as_array(
-                image.texture_descriptor.size.height / image.texture_descriptor.size.width,
-            );
-            image.texture_view_descriptor = Some(TextureViewDescriptor {
-                dimension: Some(TextureViewDimension::Cube),
-                ..default()
-            });
-        }
+/// the cube the sky is drawn on
+#[derive(SystemParam)]
+struct SkyCube<'w, 's> {
+    commands: Commands<'w, 's>,
+    meshes: ResMut<'w, Assets<Mesh>>,
+    cubemap_materials: ResMut<'w, Assets<CubemapMaterial>>,
+    cubes: Query<'w, 's, &'static Handle<CubemapMaterial>>,
+}
 
-        // spawn cube
+impl SkyCube<'_, '_> {
+    /// show a cube texture on the existing sky, spawning the cube the first time
+    fn show(&mut self, image: &Handle<Image>, exposure: f32) {
         let mut updated = false;
-        for handle in cubes.iter() {
-            if let Some(material) = cubemap_materials.get_mut(handle) {
+        for handle in self.cubes.iter() {
+            if let Some(material) = self.cubemap_materials.get_mut(handle) {
                 updated = true;
-                material.base_color_texture = Some(cubemap.image_handle.clone_weak());
+                material.base_color_texture = Some(image.clone_weak());
+                material.exposure = exposure;
             }
         }
         if !updated {
-            commands.spawn(MaterialMeshBundle::<CubemapMaterial> {
-                mesh: meshes.add(Mesh::from(shape::Cube { size: 10000.0 })),
-                material: cubemap_materials.add(CubemapMaterial {
-                    base_color_texture: Some(cubemap.image_handle.clone_weak()),
+            self.commands.spawn(MaterialMeshBundle::<CubemapMaterial> {
+                mesh: self.meshes.add(Mesh::from(shape::Cube { size: 10000.0 })),
+                material: self.cubemap_materials.add(CubemapMaterial {
+                    base_color_texture: Some(image.clone_weak()),
+                    exposure,
                 }),
                 ..default()
             });
         }
+    }
+}
+
+fn load_skybox(
+    asset_server: Res<AssetServer>,
+    mut images: ResMut<Assets<Image>>,
+    mut cubemap: ResMut<Cubemap>,
+    mut sky_cube: SkyCube,
+    mut next_sky_state: ResMut<NextState<SkyboxState>>,
+) {
+    if cubemap.is_loaded {
+        return;
+    }
+    let cube = if let Some(conversion) = cubemap.conversion.as_mut() {
+        let Some(image) = future::block_on(future::poll_once(conversion)) else {
+            return;
+        };
+        cubemap.conversion = None;
+        image.map(|image| images.add(image))
+    } else {
+        let load_state = match cubemap.layout {
+            // a cube built earlier never came from the asset server, ex: a converted panorama
+            CubemapLayout::Cube
+                if cubemap
+                    .sources
+                    .iter()
+                    .all(|handle| images.get(handle).is_some()) =>
+            {
+                LoadState::Loaded
+            }
+            _ => {
+                asset_server.get_group_load_state(cubemap.sources.iter().map(|handle| handle.id()))
+            }
+        };
+        match load_state {
+            LoadState::Loaded => {}
+            // keep the old sky, ex: a bad path or an exr that is only a git-lfs pointer
+            LoadState::Failed => {
+                for handle in cubemap.sources.iter() {
+                    if asset_server.get_load_state(handle) != LoadState::Failed {
+                        continue;
+                    }
+                    if let Some(path) = asset_server.get_handle_path(handle) {
+                        println!("Skybox image failed to load: {}", path.path().display());
+                    }
+                }
+                cubemap.is_loaded = true;
+                cubemap.sources.clear();
+                return;
+            }
+            _ => return,
+        }
 
-        cubemap.is_loaded = true;
+        // NOTE: PNGs do not have any metadata that could indicate they contain a cubemap texture,
+        // so they appear as one texture. The following code reconfigures the texture as necessary.
+        let task_pool = AsyncComputeTaskPool::get();
+        match cubemap.layout {
+            CubemapLayout::Cube => Some(cubemap.sources[0].clone()),
+            CubemapLayout::Stacked => {
+                let handle = cubemap.sources[0].clone();
+                reinterpret_stacked(images.get_mut(&handle).unwrap());
+                Some(handle)
+            }
+            // the conversion takes seconds for a 4k panorama, the task works on a copy
+            CubemapLayout::Equirectangular => {
+                let panorama = images.get(&cubemap.sources[0]).cloned();
+                cubemap.conversion = Some(
+                    task_pool
+                        .spawn(async move { panorama.as_ref().and_then(equirectangular_to_cube) }),
+                );
+                return;
+            }
+            CubemapLayout::Faces => {
+                let faces: Vec<Image> = cubemap
+                    .sources
+                    .iter()
+                    .filter_map(|handle| images.get(handle).cloned())
+                    .collect();
+                cubemap.conversion = Some(
+                    task_pool.spawn(async move { stack_faces(&faces.iter().collect::<Vec<_>>()) }),
+                );
+                return;
+            }
+        }
+    };
 
-        next_sky_state.set(SkyboxState::Loaded);
+    // keep the old sky rather than retrying every frame
+    cubemap.is_loaded = true;
+    let Some(cube) = cube else {
+        println!("Skybox images could not be made into a cubemap");
+        return;
+    };
+    cubemap.image_handle = cube;
+    // the cube texture is all that is needed now, ex: a 4k exr panorama is over 100 MB
+    cubemap.sources.clear();
+    sky_cube.show(&cubemap.image_handle, cubemap.exposure.exp2());
+
+    next_sky_state.set(SkyboxState::Loaded);
+}
+
+/// exposure can change at any time, ex: from a level
+fn apply_exposure(
+    cubemap: Res<Cubemap>,
+    mut cubemap_materials: ResMut<Assets<CubemapMaterial>>,
+    cubes: Query<&Handle<CubemapMaterial>>,
+) {
+    if !cubemap.is_changed() {
+        return;
+    }
+    let exposure = cubemap.exposure.exp2();
+    for handle in cubes.iter() {
+        if let Some(material) = cubemap_materials.get_mut(handle) {
+            material.exposure = exposure;
+        }
     }
 }
 
-pub fn set_skybox_texture(mut cubemap: ResMut<Cubemap>, image_handle: Handle<Image>) {
-    cubemap.image_handle = image_handle;
+/// load a sky from an image path, .hdr and .exr files are treated as equirectangular panoramas
+pub fn set_skybox_path(cubemap: &mut Cubemap, asset_server: &AssetServer, path: &str) {
+    set_skybox_images(
+        cubemap,
+        CubemapLayout::from_path(path),
+        vec![asset_server.load(path)],
+    );
+}
+
+/// load a sky from six images in the order +x, -x, +y, -y, +z, -z
+pub fn set_skybox_faces(cubemap: &mut Cubemap, asset_server: &AssetServer, paths: &[String; 6]) {
+    let faces = paths
+        .iter()
+        .map(|path| asset_server.load(path.as_str()))
+        .collect();
+    set_skybox_images(cubemap, CubemapLayout::Faces, faces);
+}
+
+pub fn set_skybox_images(cubemap: &mut Cubemap, layout: CubemapLayout, images: Vec<Handle<Image>>) {
+    cubemap.layout = layout;
+    cubemap.sources = images;
     cubemap.is_loaded = false;
+    // a conversion still running is for the previous sky, dropping the task cancels it
+    cubemap.conversion = None;
 }
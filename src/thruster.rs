@@ -0,0 +1,168 @@
+use bevy::prelude::*;
+use bevy_hanabi::prelude::*;
+use std::ops::Range;
+
+use crate::particles::Effects;
+use crate::player::Boost;
+use crate::velocity::{MeasuredVelocity, Velocity};
+
+pub const BOOST_EFFECT: &str = "thruster_boost";
+/// trail effects and the speed each takes over at, every stage is longer, bigger and hotter
+const THRUSTER_STAGES: [(&str, f32); 3] = [
+    ("thruster", 0.0),
+    ("thruster_fast", 35.0),
+    ("thruster_streak", 90.0),
+];
+/// particles per second while standing still
+const THRUSTER_BASE_RATE: f32 = 40.0;
+/// extra particles per second for each unit of speed, keeps the trail evenly spaced
+const THRUSTER_RATE_PER_SPEED: f32 = 4.0;
+const THRUSTER_MAX_RATE: f32 = 600.0;
+/// the effect spawner is rebuilt when the rate changes, so only change it in steps
+const THRUSTER_RATE_STEP: f32 = 20.0;
+
+pub struct ThrusterPlugin;
+
+impl Plugin for ThrusterPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(attach_thrusters)
+            .add_system(update_thrusters.after(attach_thrusters));
+    }
+}
+
+/// Where thruster emitters go on a ship, relative to the ship
+#[derive(Component)]
+pub struct ThrusterMount {
+    pub offset: Vec3,
+    attached: bool,
+}
+
+impl ThrusterMount {
+    pub fn new(offset: Vec3) -> Self {
+        Self {
+            offset,
+            attached: false,
+        }
+    }
+}
+
+/// An emitter on a ship, particles are simulated in world space so the trail stays behind
+#[derive(Component, Clone)]
+pub struct Thruster {
+    /// ship speeds this emitter runs at
+    speeds: Range<f32>,
+    /// only emits while the ship is boosting
+    boost: bool,
+    /// none until the first update, the effect starts with the rate from the library
+    rate: Option<f32>,
+}
+
+/// add the emitters once the effect library is loaded
+fn attach_thrusters(
+    mut commands: Commands,
+    mut mount_query: Query<(Entity, &mut ThrusterMount)>,
+    effects: Res<Effects>,
+) {
+    let stages: Option<Vec<Handle<EffectAsset>>> = THRUSTER_STAGES
+        .iter()
+        .map(|(name, _)| effects.handle(name))
+        .collect();
+    let (Some(stages), Some(boost_effect)) = (stages, effects.handle(BOOST_EFFECT)) else {
+        return;
+    };
+    let mut emitters: Vec<(Handle<EffectAsset>, Thruster)> = stages
+        .into_iter()
+        .enumerate()
+        .map(|(index, effect)| {
+            let start = THRUSTER_STAGES[index].1;
+            let end = THRUSTER_STAGES
+                .get(index + 1)
+                .map_or(f32::INFINITY, |(_, speed)| *speed);
+            (
+                effect,
+                Thruster {
+                    speeds: start..end,
+                    boost: false,
+                    rate: None,
+                },
+            )
+        })
+        .collect();
+    emitters.push((
+        boost_effect,
+        Thruster {
+            speeds: 0.0..f32::INFINITY,
+            boost: true,
+            rate: None,
+        },
+    ));
+
+    for (entity, mut mount) in mount_query.iter_mut() {
+        if mount.attached {
+            continue;
+        }
+        mount.attached = true;
+        commands.entity(entity).with_children(|ship| {
+            for (effect, thruster) in emitters.iter() {
+                ship.spawn((
+                    ParticleEffectBundle {
+                        effect: ParticleEffect::new(effect.clone()),
+                        transform: Transform::from_translation(mount.offset),
+                        ..default()
+                    },
+                    thruster.clone(),
+                ));
+            }
+        });
+    }
+}
+
+/// hand the trail to the stage matching the ship's speed and emit faster the faster it moves,
+/// the boost emitter only runs while boosting
+fn update_thrusters(
+    mut thruster_query: Query<(
+        &Parent,
+        &mut Thruster,
+        &mut ParticleEffect,
+        Option<&mut EffectSpawner>,
+    )>,
+    ship_query: Query<(Option<&MeasuredVelocity>, Option<&Velocity>, Option<&Boost>)>,
+    effect_assets: Res<Assets<EffectAsset>>,
+) {
+    for (parent, mut thruster, mut effect, effect_spawner) in thruster_query.iter_mut() {
+        let Ok((measured, velocity, boost)) = ship_query.get(parent.get()) else {
+            continue;
+        };
+        // the player's velocity is only the input, the measured velocity includes the rail
+        let speed = measured
+            .map(|measured| measured.value.length())
+            .or(velocity.map(|velocity| velocity.0.length()))
+            .unwrap_or(0.0);
+        let boosting = boost.is_some_and(|boost| boost.active);
+
+        let rate = if !thruster.speeds.contains(&speed) || (thruster.boost && !boosting) {
+            0.0
+        } else {
+            let rate =
+                (THRUSTER_BASE_RATE + speed * THRUSTER_RATE_PER_SPEED).min(THRUSTER_MAX_RATE);
+            (rate / THRUSTER_RATE_STEP).round() * THRUSTER_RATE_STEP
+        };
+        if thruster.rate == Some(rate) {
+            continue;
+        }
+        thruster.rate = Some(rate);
+        // hanabi only reads the instance's spawner when it creates the effect spawner,
+        // once that exists it is stopped or replaced with one at the new rate
+        effect.spawner = Some(Spawner::rate(rate.into()).with_starts_active(rate > 0.0));
+        let (Some(mut effect_spawner), Some(asset)) =
+            (effect_spawner, effect_assets.get(&effect.handle))
+        else {
+            continue;
+        };
+        if rate > 0.0 {
+            *effect_spawner = EffectSpawner::new(asset, &effect);
+        } else {
+            effect_spawner.set_active(false);
+        }
+    }
+}
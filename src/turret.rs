@@ -0,0 +1,139 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::enemy::ScoreValue;
+use crate::enemy_registry::{EnemyAssets, EnemyRegistry};
+use crate::enemy_weapon::LineOfSight;
+use crate::events::TeardownLevelEvent;
+use crate::faction::Faction;
+use crate::health::Health;
+use crate::player::Player;
+use crate::weapon::Weapon;
+
+const TURRET_RANGE: f32 = 150.0;
+/// radians per second
+const TURRET_TURN_RATE: f32 = 1.5;
+
+pub struct TurretPlugin;
+
+impl Plugin for TurretPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(spawn_turrets)
+            .add_system(track_player)
+            .add_system(on_teardown);
+    }
+}
+
+/// Placed on level nodes with the TURRET::<enemy type> gltf extra
+#[derive(Component)]
+pub struct TurretMarker {
+    pub enemy: String,
+}
+
+/// A static enemy that turns to face the player and fires while it can see them
+#[derive(Component)]
+pub struct Turret {
+    pub range: f32,
+    pub turn_rate: f32,
+}
+
+/// spawn turrets at level markers once the enemy registry is loaded,
+/// markers are read a frame after they are added so their global transform is up to date
+fn spawn_turrets(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    marker_query: Query<(Entity, &TurretMarker, &GlobalTransform)>,
+    enemy_assets: Option<Res<EnemyAssets>>,
+    registries: Res<Assets<EnemyRegistry>>,
+) {
+    let Some(registry) = enemy_assets.and_then(|assets| registries.get(&assets.registry)) else {
+        return;
+    };
+
+    for (entity, marker, global_transform) in marker_query.iter() {
+        commands.entity(entity).remove::<TurretMarker>();
+        let Some(archetype) = registry.types.get(&marker.enemy) else {
+            println!("Unknown turret type: {}", marker.enemy);
+            continue;
+        };
+
+        commands.spawn((
+            SceneBundle {
+                scene: asset_server.load(archetype.model.as_str()),
+                transform: global_transform.compute_transform(),
+                ..default()
+            },
+            Turret {
+                range: TURRET_RANGE,
+                turn_rate: TURRET_TURN_RATE,
+            },
+            LineOfSight::default(),
+            ScoreValue(archetype.score),
+            Faction::Enemy,
+            Health::new(archetype.health),
+            Weapon::new(archetype.weapon.fire_rate, 0.0, 0.0, 0.0),
+            archetype.weapon.pattern.clone(),
+            archetype.weapon.projectile,
+            archetype.collider.to_collider(),
+            Faction::Enemy.body_groups(),
+            ActiveEvents::COLLISION_EVENTS,
+            // turrets and bullets both have no rigid body
+            ActiveCollisionTypes::default() | ActiveCollisionTypes::STATIC_STATIC,
+        ));
+    }
+}
+
+/// turn towards the player and check range and line of sight against the level
+fn track_player(
+    mut turret_query: Query<(Entity, &Turret, &mut Transform, &mut LineOfSight)>,
+    player_query: Query<&GlobalTransform, With<Player>>,
+    rapier_context: Res<RapierContext>,
+    time: Res<Time>,
+) {
+    let Some(player_transform) = player_query.iter().next() else {
+        return;
+    };
+    let player_position = player_transform.translation();
+
+    for (entity, turret, mut transform, mut line_of_sight) in turret_query.iter_mut() {
+        let to_player = player_position - transform.translation;
+        let distance = to_player.length();
+        if distance <= 0.0 {
+            continue;
+        }
+
+        let target = transform.looking_at(player_position, Vec3::Y).rotation;
+        let angle = transform.rotation.angle_between(target);
+        if angle > 0.0 {
+            let t = (turret.turn_rate * time.delta_seconds() / angle).min(1.0);
+            transform.rotation = transform.rotation.slerp(target, t);
+        }
+
+        // the view is blocked by whatever would stop the turret's shots, the player is not fixed
+        let blocked = rapier_context
+            .cast_ray(
+                transform.translation,
+                to_player / distance,
+                distance,
+                true,
+                QueryFilter::only_fixed()
+                    .exclude_sensors()
+                    .exclude_collider(entity)
+                    .groups(Faction::Enemy.projectile_groups()),
+            )
+            .is_some();
+        line_of_sight.visible = distance <= turret.range && !blocked;
+    }
+}
+
+fn on_teardown(
+    mut commands: Commands,
+    query: Query<Entity, With<Turret>>,
+    mut ev_teardown: EventReader<TeardownLevelEvent>,
+) {
+    for _ in ev_teardown.iter() {
+        for entity in query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
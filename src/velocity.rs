@@ -1,36 +1,50 @@
-use crate::constants::BOUNDS_POS;
-use bevy::prelude::*;
+use crate::rail::RailSpace;
+use bevy::{prelude::*, transform::TransformSystem};
 
 pub struct VelocityPlugin;
 
 impl Plugin for VelocityPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(velocity_movement);
+        app.add_system(velocity_movement).add_system(
+            measure_velocity
+                .in_base_set(CoreSet::PostUpdate)
+                .after(TransformSystem::TransformPropagate),
+        );
     }
 }
 
 #[derive(Component)]
 pub struct Velocity(pub Vec3);
 
+/// world space velocity measured from the global transform each frame,
+/// useful for entities that are moved by a parent (ex: the player on the rail)
+#[derive(Component, Default)]
+pub struct MeasuredVelocity {
+    pub value: Vec3,
+    last_position: Option<Vec3>,
+}
+
 /// apply velocity to transform
 fn velocity_movement(
-    mut commands: Commands,
-    mut query: Query<(&mut Transform, &Velocity, Entity)>,
-    cam_query: Query<&GlobalTransform, With<Camera>>,
+    // rail space velocities are applied relative to the rail
+    mut query: Query<(&mut Transform, &Velocity), Without<RailSpace>>,
     time: Res<Time>,
 ) {
-    for (mut transform, velocity, entity) in query.iter_mut() {
+    for (mut transform, velocity) in query.iter_mut() {
         transform.translation += velocity.0 * time.delta_seconds();
+    }
+}
 
-        for cam_transform in cam_query.iter() {
-            // delete if too far away
-            let dist = Vec3::distance(transform.translation, cam_transform.translation());
-            if dist.abs() >= BOUNDS_POS.z {
-                if let Some(entity_commands) = commands.get_entity(entity) {
-                    entity_commands.despawn_recursive();
-                }
-            }
-            break;
+/// compare the global position against last frame
+fn measure_velocity(mut query: Query<(&GlobalTransform, &mut MeasuredVelocity)>, time: Res<Time>) {
+    if time.delta_seconds() <= 0.0 {
+        return;
+    }
+    for (global_transform, mut measured) in query.iter_mut() {
+        let position = global_transform.translation();
+        if let Some(last_position) = measured.last_position {
+            measured.value = (position - last_position) / time.delta_seconds();
         }
+        measured.last_position = Some(position);
     }
 }
@@ -0,0 +1,235 @@
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use rand::prelude::*;
+use serde::Deserialize;
+
+use crate::behaviour::MovementPattern;
+use crate::constants::BOUNDS_POS;
+use crate::difficulty::Difficulty;
+use crate::enemy::{spawn_squadron, SquadronSpawn};
+use crate::enemy_registry::{EnemyAssets, EnemyRegistry};
+use crate::enemy_weapon::FirePattern;
+use crate::formation::{Formation, Squadron};
+use crate::level::{NamedPath, PlayerPath};
+use crate::player::PlayerRoot;
+use crate::pool::EntityPools;
+use crate::rail::PlayerRail;
+use crate::rng::{GameRng, RngStream};
+
+pub struct WavePlugin;
+
+impl Plugin for WavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<WaveScript>()
+            .init_asset_loader::<WaveScriptLoader>()
+            .add_system(run_wave_director);
+    }
+}
+
+/// The waves of a level, loaded from a .waves.ron file
+#[derive(Deserialize, TypeUuid, Debug)]
+#[uuid = "3b6f1c52-8a0e-4f4e-9d51-6a2f7c1e9b44"]
+pub struct WaveScript {
+    pub waves: Vec<Wave>,
+    /// start again from the first wave once the last one has spawned
+    #[serde(default)]
+    pub repeat: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Wave {
+    pub trigger: WaveTrigger,
+    /// hold this wave until every enemy of the previous wave is gone
+    #[serde(default)]
+    pub wait_for_clear: bool,
+    /// name of the enemy type in the enemy registry
+    pub enemy: String,
+    pub formation: Formation,
+    /// overrides the movement of the enemy type
+    #[serde(default)]
+    pub pattern: Option<MovementPattern>,
+    /// overrides the fire pattern of the enemy type
+    #[serde(default)]
+    pub fire_pattern: Option<FirePattern>,
+    pub count: usize,
+    /// lateral and vertical offset from the rail, random within bounds if not set
+    #[serde(default)]
+    pub offset: Option<(f32, f32)>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub enum WaveTrigger {
+    /// seconds after the previous wave spawned, or after it was cleared when waiting for it
+    Time(f32),
+    /// once the player has travelled this far along the rail
+    Distance(f32),
+}
+
+#[derive(Default)]
+pub struct WaveScriptLoader;
+
+impl AssetLoader for WaveScriptLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let script = ron::de::from_bytes::<WaveScript>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(script));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["waves.ron"]
+    }
+}
+
+/// Runs the wave script of the current level, inserted when a level is loaded
+#[derive(Resource)]
+pub struct WaveDirector {
+    pub script: Handle<WaveScript>,
+    pub next_wave: usize,
+    /// seconds since the last wave spawned or was cleared
+    elapsed: f32,
+    /// distance travelled along the rail since the level (or script repeat) started
+    travelled: f32,
+    last_rail_distance: Option<f32>,
+    last_squadron: Option<Entity>,
+}
+
+impl WaveDirector {
+    pub fn new(script: Handle<WaveScript>) -> Self {
+        Self {
+            script,
+            next_wave: 0,
+            elapsed: 0.0,
+            travelled: 0.0,
+            last_rail_distance: None,
+            last_squadron: None,
+        }
+    }
+}
+
+/// everything needed to place a wave's squadron along the rail
+#[derive(SystemParam)]
+struct WaveSpawner<'w, 's> {
+    commands: Commands<'w, 's>,
+    enemy_assets: Option<Res<'w, EnemyAssets>>,
+    registries: Res<'w, Assets<EnemyRegistry>>,
+    asset_server: Res<'w, AssetServer>,
+    pools: ResMut<'w, EntityPools>,
+    rail: Res<'w, PlayerRail>,
+    player_path_query: Query<'w, 's, &'static PlayerPath, Without<NamedPath>>,
+    game_rng: ResMut<'w, GameRng>,
+    difficulty: Res<'w, Difficulty>,
+}
+
+/// spawn the next wave once its trigger and conditions are met, at most one wave per frame
+fn run_wave_director(
+    director: Option<ResMut<WaveDirector>>,
+    scripts: Res<Assets<WaveScript>>,
+    mut spawner: WaveSpawner,
+    player_query: Query<(), With<PlayerRoot>>,
+    squadron_query: Query<(), With<Squadron>>,
+    time: Res<Time>,
+) {
+    let Some(mut director) = director else {
+        return;
+    };
+    // waves only run once the player is on the rail
+    if player_query.is_empty() {
+        return;
+    }
+    let Some(script) = scripts.get(&director.script) else {
+        return;
+    };
+    let Some(registry) = spawner
+        .enemy_assets
+        .as_ref()
+        .and_then(|assets| spawner.registries.get(&assets.registry))
+    else {
+        return;
+    };
+    let rail = &spawner.rail;
+    let difficulty = &spawner.difficulty;
+
+    if let Some(last_rail_distance) = director.last_rail_distance {
+        director.travelled += -rail.distance_to(last_rail_distance);
+    }
+    director.last_rail_distance = Some(rail.distance);
+    director.elapsed += time.delta_seconds();
+
+    if director.next_wave >= script.waves.len() {
+        if !script.repeat || script.waves.is_empty() {
+            return;
+        }
+        director.next_wave = 0;
+        director.travelled = 0.0;
+    }
+    let wave = &script.waves[director.next_wave];
+
+    if wave.wait_for_clear {
+        if let Some(last_squadron) = director.last_squadron {
+            if squadron_query.contains(last_squadron) {
+                // the timer starts once the previous wave is cleared
+                director.elapsed = 0.0;
+                return;
+            }
+        }
+    }
+
+    let triggered = match wave.trigger {
+        // denser spawns bring timed waves in sooner
+        WaveTrigger::Time(seconds) => director.elapsed >= seconds / difficulty.spawn_density(),
+        WaveTrigger::Distance(distance) => director.travelled >= distance,
+    };
+    if !triggered {
+        return;
+    }
+
+    let Some(archetype) = registry.types.get(&wave.enemy) else {
+        println!("Unknown enemy type: {}", wave.enemy);
+        director.next_wave += 1;
+        return;
+    };
+
+    let offset = match wave.offset {
+        Some((x, y)) => Vec2::new(x, y),
+        None => {
+            let rng = spawner.game_rng.stream(RngStream::Spawns);
+            Vec2::new(
+                rng.gen_range(-BOUNDS_POS.x..BOUNDS_POS.x),
+                rng.gen_range(-BOUNDS_POS.y..BOUNDS_POS.y),
+            )
+        }
+    };
+
+    let count = ((wave.count as f32 * difficulty.spawn_density()).round() as usize).max(1);
+    let squadron = spawn_squadron(
+        &mut spawner.commands,
+        &mut spawner.pools,
+        &spawner.asset_server,
+        rail,
+        spawner.player_path_query.iter().next(),
+        &SquadronSpawn {
+            archetype,
+            formation: wave.formation,
+            pattern: wave.pattern.clone().unwrap_or(archetype.movement.clone()),
+            fire_pattern: wave
+                .fire_pattern
+                .clone()
+                .unwrap_or(archetype.weapon.pattern.clone()),
+            count,
+            offset,
+        },
+    );
+
+    director.last_squadron = Some(squadron);
+    director.elapsed = 0.0;
+    director.next_wave += 1;
+}
@@ -0,0 +1,129 @@
+use bevy::prelude::*;
+
+use crate::math::move_toward_f32;
+
+/// heat is normalized, a weapon overheats once it reaches this value
+pub const MAX_HEAT: f32 = 1.0;
+
+pub struct WeaponPlugin;
+
+impl Plugin for WeaponPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(cool_weapons);
+    }
+}
+
+/// Fire rate limiting and heat management for anything that shoots
+#[derive(Component)]
+pub struct Weapon {
+    /// shots per second while the trigger is held
+    pub fire_rate: f32,
+    /// heat added by every shot
+    pub heat_per_shot: f32,
+    /// heat removed every second
+    pub cooling_rate: f32,
+    /// an overheated weapon is locked until heat drops below this value
+    pub recover_heat: f32,
+    pub heat: f32,
+    pub overheated: bool,
+    cooldown: f32,
+}
+
+impl Weapon {
+    pub fn new(fire_rate: f32, heat_per_shot: f32, cooling_rate: f32, recover_heat: f32) -> Self {
+        Self {
+            fire_rate,
+            heat_per_shot,
+            cooling_rate,
+            recover_heat,
+            heat: 0.0,
+            overheated: false,
+            cooldown: 0.0,
+        }
+    }
+
+    pub fn can_fire(&self) -> bool {
+        !self.overheated && self.cooldown <= 0.0
+    }
+
+    /// returns true if a shot was fired, adding heat and starting the fire rate cooldown
+    pub fn try_fire(&mut self) -> bool {
+        if !self.can_fire() {
+            return false;
+        }
+
+        self.cooldown += 1.0 / self.fire_rate;
+        self.heat += self.heat_per_shot;
+        if self.heat >= MAX_HEAT {
+            self.heat = MAX_HEAT;
+            self.overheated = true;
+        }
+        true
+    }
+
+    /// tick down the fire rate cooldown and dissipate heat over `dt` seconds
+    fn cool(&mut self, dt: f32) {
+        // the cooldown may dip below zero so the leftover carries into the next shot and the
+        // average rate doesn't snap to whole frames, clamped so an idle weapon can't bank shots
+        self.cooldown = (self.cooldown - dt).max(-dt);
+        self.heat = move_toward_f32(self.heat, 0.0, self.cooling_rate * dt);
+        if self.overheated && self.heat <= self.recover_heat {
+            self.overheated = false;
+        }
+    }
+}
+
+fn cool_weapons(mut query: Query<&mut Weapon>, time: Res<Time>) {
+    let dt = time.delta_seconds();
+    for mut weapon in query.iter_mut() {
+        weapon.cool(dt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fire_rate_limits_shots() {
+        let mut weapon = Weapon::new(4.0, 0.25, 0.5, 0.5);
+        assert!(weapon.try_fire());
+        assert!(!weapon.try_fire());
+        weapon.cool(0.125);
+        assert!(!weapon.try_fire());
+        weapon.cool(0.125);
+        assert!(weapon.try_fire());
+    }
+
+    #[test]
+    fn heat_accumulates_per_shot() {
+        let mut weapon = Weapon::new(4.0, 0.25, 0.0, 0.5);
+        for shot in 1..=3 {
+            assert!(weapon.try_fire());
+            assert_eq!(weapon.heat, 0.25 * shot as f32);
+            weapon.cool(0.25);
+        }
+        assert!(!weapon.overheated);
+    }
+
+    #[test]
+    fn overheating_locks_until_below_recover_heat() {
+        let mut weapon = Weapon::new(4.0, 0.25, 0.0, 0.5);
+        for _ in 0..4 {
+            assert!(weapon.try_fire());
+            weapon.cool(0.25);
+        }
+        assert!(weapon.overheated);
+        assert_eq!(weapon.heat, MAX_HEAT);
+        assert!(!weapon.try_fire());
+
+        // the cooldown has run out, only the heat keeps it locked
+        weapon.cooling_rate = 0.5;
+        weapon.cool(0.5);
+        assert_eq!(weapon.heat, 0.75);
+        assert!(!weapon.try_fire());
+        weapon.cool(0.5);
+        assert!(!weapon.overheated);
+        assert!(weapon.try_fire());
+    }
+}